@@ -1,3 +1,4 @@
+import re
 import sys
 import uuid
 import pyautogui
@@ -17,22 +18,23 @@ rust_file_path = "".join([root, "\\src\\bin\\", shader_path.split("/")[1], "\\sh
 with open(rust_file_path) as rust_file:
     rust_file_contents = rust_file.readlines()
 
-uuid_line = None
+# every module compiled from the shader (one per define permutation) has an edit_id on the line after its path
+uuid_lines = []
 for i, line in enumerate(rust_file_contents):
     if shader_path in line:
-        uuid_line = i + 1
+        uuid_lines.append(i + 1)
 
-if uuid_line is None:
+if not uuid_lines:
     raise Exception("Cannot find line to replace")
 
-valid_uuid = str(uuid.uuid4())
-valid_uuid = valid_uuid.replace("0", "x")
-valid_uuid = valid_uuid.replace("f", "x")
+for uuid_line in uuid_lines:
+    valid_uuid = str(uuid.uuid4())
+    valid_uuid = valid_uuid.replace("0", "x")
+    valid_uuid = valid_uuid.replace("f", "x")
 
-uuid_line_start = rust_file_contents[uuid_line].split("[")[0]
-uuid_line_end = '[("edit_id", "__X__")]\n'.replace("__X__", valid_uuid)
-
-rust_file_contents[uuid_line] = "".join([uuid_line_start, uuid_line_end])
+    rust_file_contents[uuid_line] = re.sub(r'\("edit_id", "[^"]*"\)',
+                                           '("edit_id", "__X__")'.replace("__X__", valid_uuid),
+                                           rust_file_contents[uuid_line])
 
 with open(rust_file_path, "w") as rust_file:
     rust_file.writelines(rust_file_contents)
@@ -1,6 +1,7 @@
 #version 460
 
-layout(local_size_x = 1024, local_size_y = 1, local_size_z = 1) in;
+// the local size is set through specialization constants by ComputeKernel
+layout(local_size_x_id = 0, local_size_y_id = 1, local_size_z_id = 2) in;
 
 layout(set = 0, binding = 0) buffer Data {
     uint data[];
@@ -8,5 +9,8 @@ layout(set = 0, binding = 0) buffer Data {
 
 void main() {
     uint index = gl_GlobalInvocationID.x;
+    if (index >= uint(buf.data.length())) {
+        return;
+    }
     buf.data[index] *= 2;
-}
\ No newline at end of file
+}
@@ -1,6 +1,7 @@
 #version 460
 
-layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+// the local size is set through specialization constants by ComputeKernel
+layout(local_size_x_id = 0, local_size_y_id = 1, local_size_z_id = 2) in;
 
 layout(set = 0, binding = 0, rgba8) uniform writeonly image2D img;
 
@@ -0,0 +1,37 @@
+#ifndef LIGHTING_GLSL
+#define LIGHTING_GLSL
+
+const float PI = 3.14159265359;
+const vec3 AMBIENT_COLOR = vec3(13) / 255;
+const vec3 LIGHT_COLOR = vec3(3.0);
+
+// Cook-Torrance with a GGX distribution, Smith-Schlick geometry and Schlick fresnel, for a single white point
+// light without falloff, plus a constant ambient term
+vec3 pbr_lighting(vec3 base_color, float metallic, float roughness, vec3 normal, vec3 position, vec3 light_pos, vec3 eye_pos) {
+     vec3 light_dir = normalize(light_pos - position);
+     vec3 eye_dir = normalize(eye_pos - position);
+     vec3 half_dir = normalize(light_dir + eye_dir);
+
+     float n_dot_l = max(dot(normal, light_dir), 0);
+     float n_dot_v = max(dot(normal, eye_dir), 1e-4);
+     float n_dot_h = max(dot(normal, half_dir), 0);
+     float v_dot_h = max(dot(eye_dir, half_dir), 0);
+
+     float alpha = max(roughness * roughness, 1e-3);
+     float alpha_2 = alpha * alpha;
+     float d_denominator = n_dot_h * n_dot_h * (alpha_2 - 1) + 1;
+     float distribution = alpha_2 / (PI * d_denominator * d_denominator);
+
+     float k = (roughness + 1) * (roughness + 1) / 8;
+     float geometry = (n_dot_l / (n_dot_l * (1 - k) + k)) * (n_dot_v / (n_dot_v * (1 - k) + k));
+
+     vec3 f0 = mix(vec3(0.04), base_color, metallic);
+     vec3 fresnel = f0 + (1 - f0) * pow(1 - v_dot_h, 5);
+
+     vec3 specular = distribution * geometry * fresnel / (4 * n_dot_l * n_dot_v + 1e-4);
+     vec3 diffuse = (1 - fresnel) * (1 - metallic) * base_color / PI;
+
+     return AMBIENT_COLOR * base_color + (diffuse + specular) * LIGHT_COLOR * n_dot_l;
+}
+
+#endif
@@ -0,0 +1,22 @@
+#ifndef UNIFORMS_GLSL
+#define UNIFORMS_GLSL
+
+layout(set = 0, binding = 0) uniform VertexData {
+    mat4 mvp;
+} vertex_uniforms;
+
+layout(set = 0, binding = 1) uniform FragmentData {
+    vec3 light_pos;
+    vec3 eye_pos;
+} fragment_uniforms;
+
+// per draw: the world transform of the mesh and its material factors
+layout(push_constant) uniform DrawData {
+    mat4 model;
+    vec4 base_color_factor;
+    vec3 emissive_factor;
+    float metallic_factor;
+    float roughness_factor;
+} draw;
+
+#endif
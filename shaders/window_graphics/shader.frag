@@ -1,32 +1,36 @@
 #version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include <uniforms.glsl>
+#include <lighting.glsl>
 
 layout(location = 0) in vec3 f_normal;
 layout(location = 1) in vec3 f_position;
+layout(location = 2) in vec2 f_tex_coord;
+// linear, white unless the mesh has vertex colors
+layout(location = 3) in vec4 f_vertex_color;
 
 layout(location = 0) out vec4 f_color;
 
-layout(set = 0, binding = 1) uniform FragmentData {
-     vec3 light_pos;
-     vec3 eye_pos;
-} uniforms;
+#ifndef SHOW_NORMALS
+// white when the material has no texture
+layout(set = 1, binding = 0) uniform sampler2D base_color_texture;
+layout(set = 1, binding = 1) uniform sampler2D metallic_roughness_texture;
+#endif
 
 void main() {
-     // f_color = vec4((f_normal + 1) / 2, 1.0);
-
-     vec3 ambient = vec3(13) / 255;
-     vec3 diffuse = vec3(204) / 255;
-     vec3 specular = vec3(255) / 255;
-
-     vec3 light_dir = normalize(uniforms.light_pos - f_position);
-     vec3 eye_dir = normalize(uniforms.eye_pos - f_position);
-
-     float diffuse_coef = max(dot(f_normal, light_dir), 0);
-     float specular_coef = 0;
-     if (diffuse_coef > 0) {
-          vec3 refl_light_dir = reflect(-light_dir, f_normal);
-          specular_coef = max(dot(eye_dir, refl_light_dir), 0);
-          specular_coef = pow(specular_coef, 50);
-     }
-
-     f_color = vec4(ambient + diffuse_coef * diffuse + specular_coef * specular, 1.0);
+     vec3 normal = normalize(f_normal);
+
+#ifdef SHOW_NORMALS
+     f_color = vec4((normal + 1) / 2, 1.0);
+#else
+     vec4 base_color = draw.base_color_factor * f_vertex_color * texture(base_color_texture, f_tex_coord);
+     vec4 metallic_roughness = texture(metallic_roughness_texture, f_tex_coord);
+     float metallic = draw.metallic_factor * metallic_roughness.b;
+     float roughness = draw.roughness_factor * metallic_roughness.g;
+
+     vec3 color = pbr_lighting(base_color.rgb, metallic, roughness, normal, f_position,
+                               fragment_uniforms.light_pos, fragment_uniforms.eye_pos);
+     f_color = vec4(color + draw.emissive_factor, base_color.a);
+#endif
 }
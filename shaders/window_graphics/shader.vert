@@ -1,17 +1,23 @@
 #version 460
+#extension GL_GOOGLE_include_directive : require
+
+#include <uniforms.glsl>
 
 layout(location = 0) in vec3 position;
 layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 tex_coord;
+layout(location = 3) in vec4 color;
 
 layout(location = 0) out vec3 f_normal;
 layout(location = 1) out vec3 f_position;
-
-layout(set = 0, binding = 0) uniform VertexData {
-    mat4 mvp;
-} uniforms;
+layout(location = 2) out vec2 f_tex_coord;
+layout(location = 3) out vec4 f_vertex_color;
 
 void main() {
-    f_normal = normalize(normal);
-    f_position = position;
-    gl_Position = uniforms.mvp * vec4(position, 1.0);
+    vec4 world_position = draw.model * vec4(position, 1.0);
+    f_normal = normalize(transpose(inverse(mat3(draw.model))) * normal);
+    f_position = world_position.xyz;
+    f_tex_coord = tex_coord;
+    f_vertex_color = color;
+    gl_Position = vertex_uniforms.mvp * world_position;
 }
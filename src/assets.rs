@@ -0,0 +1,28 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+const ASSET_DIR_ENV_VAR: &str = "VKPG_ASSET_DIR";
+
+// Shaders compiled with vulkano_shaders::shader! are embedded as SPIR-V and resolve their path relative to
+// CARGO_MANIFEST_DIR at build time. Files read at runtime go through these functions instead, which look in
+// VKPG_ASSET_DIR when set and otherwise in the crate root the binary was built from, so the working
+// directory does not matter.
+
+pub fn asset_dir() -> PathBuf {
+    env::var_os(ASSET_DIR_ENV_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")))
+}
+
+// forward slashes work as separators on every OS
+pub fn asset_path(relative_path: impl AsRef<Path>) -> PathBuf {
+    asset_dir().join(relative_path)
+}
+
+pub fn shader_path(relative_path: impl AsRef<Path>) -> PathBuf {
+    asset_path(Path::new("shaders").join(relative_path))
+}
+
+pub fn resource_path(relative_path: impl AsRef<Path>) -> PathBuf {
+    asset_path(Path::new("resources").join(relative_path))
+}
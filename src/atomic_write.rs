@@ -0,0 +1,17 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// writes next to the old file first and renames over it, so an interrupted save never leaves a truncated file behind;
+// creates the parent directories
+pub(crate) fn write_atomically(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut temporary_file_name = path.file_name().map(OsString::from).unwrap_or_default();
+    temporary_file_name.push(".tmp");
+    let temporary_path = path.with_file_name(temporary_file_name);
+    fs::write(&temporary_path, bytes)?;
+    fs::rename(&temporary_path, path)
+}
@@ -0,0 +1,165 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+use log::info;
+use serde::Serialize;
+
+#[derive(Clone, Copy, Debug)]
+pub struct BenchmarkConfig {
+    pub warmup_iterations: u32,
+    pub iterations: u32,
+}
+
+impl Default for BenchmarkConfig {
+    fn default() -> Self {
+        BenchmarkConfig {
+            warmup_iterations: 2,
+            iterations: 10,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct SweepPoint {
+    pub num_values: u32,
+    // None for targets that do not work in batches, such as the CPU reference
+    pub batch_size: Option<u32>,
+}
+
+// phases a target does not have are left at None
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PhaseTimings {
+    pub upload: Option<Duration>,
+    pub dispatch: Option<Duration>,
+    // measured on the GPU itself, see TimestampQueries
+    pub gpu_dispatch: Option<Duration>,
+    pub readback: Option<Duration>,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct DurationStats {
+    pub min_ms: f64,
+    pub median_ms: f64,
+    pub p95_ms: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct BenchmarkRow {
+    pub target: String,
+    #[serde(flatten)]
+    pub sweep_point: SweepPoint,
+    pub phase: &'static str,
+    pub iterations: u32,
+    #[serde(flatten)]
+    pub stats: DurationStats,
+}
+
+pub struct Benchmark {
+    config: BenchmarkConfig,
+    rows: Vec<BenchmarkRow>,
+}
+
+impl DurationStats {
+    // nearest-rank percentiles
+    pub fn from_durations(durations: &[Duration]) -> Option<Self> {
+        if durations.is_empty() {
+            return None;
+        }
+
+        let mut sorted = durations.to_vec();
+        sorted.sort();
+        let percentile = |p: f64| {
+            let rank = (p * sorted.len() as f64).ceil() as usize;
+            sorted[rank.clamp(1, sorted.len()) - 1].as_secs_f64() * 1000.0
+        };
+
+        Some(DurationStats {
+            min_ms: sorted[0].as_secs_f64() * 1000.0,
+            median_ms: percentile(0.5),
+            p95_ms: percentile(0.95),
+        })
+    }
+}
+
+impl Benchmark {
+    pub fn new(config: BenchmarkConfig) -> Self {
+        Benchmark {
+            config,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn rows(&self) -> &[BenchmarkRow] {
+        &self.rows
+    }
+
+    // runs the iteration warmup_iterations times without recording, then iterations times with
+    pub fn run(&mut self, target: &str, sweep_point: SweepPoint, mut iteration: impl FnMut() -> PhaseTimings) {
+        for _ in 0..self.config.warmup_iterations {
+            iteration();
+        }
+
+        let timings = (0..self.config.iterations).map(|_| iteration()).collect::<Vec<_>>();
+
+        let phases: [(&'static str, fn(&PhaseTimings) -> Option<Duration>); 4] = [
+            ("upload", |timing| timing.upload),
+            ("dispatch", |timing| timing.dispatch),
+            ("gpu_dispatch", |timing| timing.gpu_dispatch),
+            ("readback", |timing| timing.readback),
+        ];
+        for (phase, get_duration) in phases {
+            let durations = timings.iter().filter_map(get_duration).collect::<Vec<_>>();
+            let Some(stats) = DurationStats::from_durations(&durations) else {
+                continue;
+            };
+
+            info!("{:<4} values: {:>9}, batch size: {:>5} | {:<12} | min: {:8.3}ms, median: {:8.3}ms, p95: {:8.3}ms",
+                  target,
+                  sweep_point.num_values,
+                  sweep_point.batch_size.map(|batch_size| batch_size.to_string()).unwrap_or_else(|| "--".to_string()),
+                  phase,
+                  stats.min_ms,
+                  stats.median_ms,
+                  stats.p95_ms);
+
+            self.rows.push(BenchmarkRow {
+                target: target.to_string(),
+                sweep_point,
+                phase,
+                iterations: durations.len() as u32,
+                stats,
+            });
+        }
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("target,num_values,batch_size,phase,iterations,min_ms,median_ms,p95_ms\n");
+        for row in &self.rows {
+            writeln!(csv, "{},{},{},{},{},{:.6},{:.6},{:.6}",
+                     row.target,
+                     row.sweep_point.num_values,
+                     row.sweep_point.batch_size.map(|batch_size| batch_size.to_string()).unwrap_or_default(),
+                     row.phase,
+                     row.iterations,
+                     row.stats.min_ms,
+                     row.stats.median_ms,
+                     row.stats.p95_ms
+            ).unwrap();
+        }
+        csv
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.rows).unwrap()
+    }
+
+    pub fn write_csv(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_csv())
+    }
+
+    pub fn write_json(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_json())
+    }
+}
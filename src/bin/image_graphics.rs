@@ -1,5 +1,7 @@
+use std::process::{exit, ExitCode};
 use image::{ImageBuffer, Rgba};
 use log::info;
+use vulkan_playground::ValidationFailure;
 use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage};
 use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo, RenderPassBeginInfo, SubpassBeginInfo, SubpassContents, SubpassEndInfo};
 use vulkano::format::Format;
@@ -29,7 +31,16 @@ struct BasicVertex {
     position: [f32; 2]
 }
 
-fn main() {
+fn main() -> ExitCode {
+    vulkan_playground::logging::init_logger();
+
+    let vulkan_items = vulkan_playground::CommonItemsBuilder::new()
+        .with_queue_flags(QueueFlags::GRAPHICS)
+        .build()
+        .unwrap_or_else(|error| {
+            eprintln!("{error}");
+            exit(1)
+        });
     let vulkan_playground::CommonItems {
         library: _,
         instance: _,
@@ -38,8 +49,11 @@ fn main() {
         queue,
         memory_allocator,
         descriptor_set_allocator: _,
-        command_buffer_allocator
-    } = vulkan_playground::get_common_vulkan_items(None, None, None, QueueFlags::GRAPHICS, None);
+        command_buffer_allocator,
+        validation_report,
+        pipeline_cache,
+        ..
+    } = &vulkan_items;
 
     let vertex1 = BasicVertex { position: [0.0, -0.5]};
     let vertex2 = BasicVertex { position: [0.5, 0.0]};
@@ -156,7 +170,7 @@ fn main() {
 
     let graphics_pipeline = GraphicsPipeline::new(
         device.clone(),
-        None,
+        pipeline_cache.clone(),
         GraphicsPipelineCreateInfo {
             stages: stages.into_iter().collect(),
             vertex_input_state: Some(vertex_input_state),
@@ -215,5 +229,8 @@ fn main() {
     ).unwrap();
     image_buffer.save("image_graphics.png").unwrap();
 
-    info!("Success")
+    info!("Success");
+
+    vulkan_items.save_pipeline_cache();
+    validation_report.finish_run().map_or_else(ValidationFailure::exit_code, |()| ExitCode::SUCCESS)
 }
\ No newline at end of file
@@ -0,0 +1,97 @@
+use std::f32::consts::FRAC_PI_2;
+use glam::{Mat4, Vec3, Vec4};
+use crate::camera_controller::CameraController;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ProjectionMode {
+    Perspective,
+    Orthographic,
+}
+
+impl ProjectionMode {
+    pub const ALL: [ProjectionMode; 2] = [ProjectionMode::Perspective, ProjectionMode::Orthographic];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ProjectionMode::Perspective => "Perspective",
+            ProjectionMode::Orthographic => "Orthographic",
+        }
+    }
+}
+
+// the camera the user moves, with the projection it draws with
+pub struct Camera {
+    pub controller: CameraController,
+    pub projection_mode: ProjectionMode,
+    // vertical, in radians; orthographic views show what a perspective one would at the focus distance
+    pub fov_y: f32,
+    pub near: f32,
+    pub far: f32,
+    // depth 1 at the near plane and 0 at the far one, which spreads the float depth precision over the distance
+    pub reverse_z: bool,
+}
+
+impl Camera {
+    pub const DEFAULT_FOV_Y: f32 = FRAC_PI_2;
+    pub const DEFAULT_NEAR: f32 = 0.1;
+    pub const DEFAULT_FAR: f32 = 1000.0;
+    // a sphere smaller than this is framed as if it had this radius, so a single point still gets a view
+    const MIN_FRAMED_RADIUS: f32 = 0.01;
+
+    pub fn new(controller: CameraController) -> Self {
+        Camera {
+            controller,
+            projection_mode: ProjectionMode::Perspective,
+            fov_y: Self::DEFAULT_FOV_Y,
+            near: Self::DEFAULT_NEAR,
+            far: Self::DEFAULT_FAR,
+            reverse_z: false,
+        }
+    }
+
+    pub fn projection_matrix(&self, aspect_ratio: f32) -> Mat4 {
+        let projection = match self.projection_mode {
+            ProjectionMode::Perspective => Mat4::perspective_lh(self.fov_y, aspect_ratio, self.near, self.far),
+            ProjectionMode::Orthographic => {
+                let half_height = self.controller.focus_distance() * (self.fov_y / 2.0).tan();
+                let half_width = half_height * aspect_ratio;
+                Mat4::orthographic_lh(-half_width, half_width, -half_height, half_height, self.near, self.far)
+            }
+        };
+        self.with_depth_convention(projection)
+    }
+
+    pub fn view_projection(&self, aspect_ratio: f32) -> Mat4 {
+        self.projection_matrix(aspect_ratio) * self.controller.view_matrix()
+    }
+
+    // for projections made elsewhere, like those of scene cameras, which map the near plane to depth 0
+    pub fn with_depth_convention(&self, projection: Mat4) -> Mat4 {
+        if !self.reverse_z {
+            return projection;
+        }
+        // depth becomes 1 - depth, after the perspective divide
+        let reverse_depth = Mat4::from_cols(Vec4::X, Vec4::Y, Vec4::NEG_Z, Vec4::new(0.0, 0.0, 1.0, 1.0));
+        reverse_depth * projection
+    }
+
+    // the normalized device depth of the near and far plane
+    pub fn near_far_depth(&self) -> (f32, f32) {
+        if self.reverse_z { (1.0, 0.0) } else { (0.0, 1.0) }
+    }
+
+    // moves the camera back along its view direction until the sphere fills the view, and fits the clip planes
+    // around it so large meshes are not cut off
+    pub fn frame_sphere(&mut self, center: Vec3, radius: f32, aspect_ratio: f32) {
+        let radius = radius.max(Self::MIN_FRAMED_RADIUS);
+
+        // the narrower of the two fields of view
+        let half_fov_y = self.fov_y / 2.0;
+        let half_fov_x = (half_fov_y.tan() * aspect_ratio).atan();
+        let distance = radius / half_fov_y.min(half_fov_x).sin();
+
+        self.controller.frame(center, distance);
+        self.near = radius * 0.01;
+        self.far = (distance + radius) * 100.0;
+    }
+}
@@ -0,0 +1,98 @@
+use glam::{Mat4, Vec3};
+use crate::fly_camera::FlyCamera;
+use crate::orbit_camera::OrbitCamera;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CameraMode {
+    Orbit,
+    Fly,
+}
+
+impl CameraMode {
+    pub const ALL: [CameraMode; 2] = [CameraMode::Orbit, CameraMode::Fly];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CameraMode::Orbit => "Orbit",
+            CameraMode::Fly => "Fly",
+        }
+    }
+
+    pub fn toggled(self) -> Self {
+        match self {
+            CameraMode::Orbit => CameraMode::Fly,
+            CameraMode::Fly => CameraMode::Orbit,
+        }
+    }
+}
+
+// the camera the user moves, in one of the modes at a time
+pub struct CameraController {
+    mode: CameraMode,
+    pub orbit: OrbitCamera,
+    pub fly: FlyCamera,
+}
+
+impl CameraController {
+    pub fn new(eye_pos: Vec3, target: Vec3) -> Self {
+        CameraController {
+            mode: CameraMode::Orbit,
+            orbit: OrbitCamera::looking_at(target, eye_pos),
+            fly: FlyCamera::new(eye_pos, target - eye_pos),
+        }
+    }
+
+    pub fn mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    // keeps the eye position and view direction; the orbit pivot ends up in front of the camera,
+    // at the distance it had before flying
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if mode == self.mode {
+            return;
+        }
+
+        match mode {
+            CameraMode::Orbit => {
+                let pivot = self.fly.position + self.fly.forward() * self.orbit.distance;
+                self.orbit.recenter(pivot, self.fly.position);
+            }
+            CameraMode::Fly => {
+                self.fly.position = self.orbit.eye_pos();
+                self.fly.look_along(self.orbit.pivot - self.fly.position);
+                self.fly.stop();
+            }
+        }
+        self.mode = mode;
+    }
+
+    pub fn eye_pos(&self) -> Vec3 {
+        match self.mode {
+            CameraMode::Orbit => self.orbit.eye_pos(),
+            CameraMode::Fly => self.fly.position,
+        }
+    }
+
+    // how far away the camera looks; the orbit distance, which fly mode leaves as it was
+    pub fn focus_distance(&self) -> f32 {
+        self.orbit.distance
+    }
+
+    // puts the target at the distance straight ahead, keeping the view direction
+    pub fn frame(&mut self, target: Vec3, distance: f32) {
+        self.orbit.pivot = target;
+        self.orbit.distance = distance;
+        if self.mode == CameraMode::Fly {
+            self.fly.position = target - self.fly.forward() * distance;
+            self.fly.stop();
+        }
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        match self.mode {
+            CameraMode::Orbit => self.orbit.view_matrix(),
+            CameraMode::Fly => self.fly.view_matrix(),
+        }
+    }
+}
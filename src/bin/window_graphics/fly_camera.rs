@@ -0,0 +1,83 @@
+use std::f32::consts::FRAC_PI_2;
+use glam::{Mat4, Vec3};
+
+// first-person camera that flies where it looks; yaw turns around the world Y axis, pitch tilts toward it
+pub struct FlyCamera {
+    pub position: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+    // world units per second, before speed modifiers
+    pub speed: f32,
+    velocity: Vec3,
+}
+
+impl FlyCamera {
+    // the same limit as the orbit camera, so switching between them keeps the view
+    const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+    // the time the velocity takes to get about two thirds of the way to the one the keys ask for
+    const SMOOTHING_TIME: f32 = 0.08;
+    const MIN_SPEED: f32 = 0.01;
+    const MAX_SPEED: f32 = 1000.0;
+
+    pub fn new(position: Vec3, forward: Vec3) -> Self {
+        let mut fly_camera = FlyCamera {
+            position,
+            yaw: 0.0,
+            pitch: 0.0,
+            speed: 1.0,
+            velocity: Vec3::ZERO,
+        };
+        fly_camera.look_along(forward);
+        fly_camera
+    }
+
+    pub fn forward(&self) -> Vec3 {
+        Vec3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        )
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        Mat4::look_to_lh(self.position, self.forward(), Vec3::NEG_Y)
+    }
+
+    pub fn look_along(&mut self, forward: Vec3) {
+        let forward = forward.normalize_or_zero();
+        if forward == Vec3::ZERO {
+            return;
+        }
+        self.yaw = forward.x.atan2(forward.z);
+        self.pitch = forward.y.asin().clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+    }
+
+    // in radians, positive yaw turns right and positive pitch looks up
+    pub fn turn(&mut self, yaw: f32, pitch: f32) {
+        self.yaw -= yaw;
+        self.pitch = (self.pitch + pitch).clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+    }
+
+    // movement is x to the right, y along the world up axis and z forward, and flies at full speed from a length
+    // of 1, so analog sticks fly slower when partly tilted; the velocity eases toward it instead of jumping
+    pub fn fly(&mut self, movement: Vec3, speed_factor: f32, frame_duration: f32) {
+        let forward = self.forward();
+        let right = Vec3::NEG_Y.cross(forward).normalize();
+        let movement = movement.clamp_length_max(1.0);
+        let direction = right * movement.x + Vec3::Y * movement.y + forward * movement.z;
+        let target_velocity = direction * self.speed * speed_factor;
+
+        let blend = 1.0 - (-frame_duration / Self::SMOOTHING_TIME).exp();
+        self.velocity = self.velocity.lerp(target_velocity, blend);
+        self.position += self.velocity * frame_duration;
+    }
+
+    // factors above 1 fly faster
+    pub fn scale_speed(&mut self, factor: f32) {
+        self.speed = (self.speed * factor).clamp(Self::MIN_SPEED, Self::MAX_SPEED);
+    }
+
+    pub fn stop(&mut self) {
+        self.velocity = Vec3::ZERO;
+    }
+}
@@ -0,0 +1,103 @@
+use gilrs::{Axis, Button, Event, EventType, GamepadId, Gilrs};
+use glam::Vec2;
+use log::{info, warn};
+use crate::input_actions::GamepadButton;
+
+// analog state of the gamepad used last, all zero without one
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GamepadAxes {
+    // right and up are positive, gilrs already applied the dead zone
+    pub left_stick: Vec2,
+    pub right_stick: Vec2,
+    // from 0 when released to 1 when fully pressed
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+}
+
+// buttons go through the input actions, the sticks and triggers are read directly
+pub struct Gamepads {
+    // None when the platform has no gamepad support
+    gilrs: Option<Gilrs>,
+    active: Option<GamepadId>,
+}
+
+impl Gamepads {
+    pub fn new() -> Self {
+        let gilrs = Gilrs::new()
+            .inspect_err(|error| warn!("Gamepad input disabled: {error}"))
+            .ok();
+
+        Gamepads {
+            gilrs,
+            active: None,
+        }
+    }
+
+    // reports the button presses and releases since the last poll, and returns the axes as they are now
+    pub fn poll(&mut self, mut on_button: impl FnMut(GamepadButton, bool)) -> GamepadAxes {
+        let Some(gilrs) = self.gilrs.as_mut() else {
+            return GamepadAxes::default();
+        };
+
+        while let Some(Event { id, event, .. }) = gilrs.next_event() {
+            match event {
+                EventType::Connected => info!("Gamepad connected: {}", gilrs.gamepad(id).name()),
+                EventType::Disconnected => {
+                    info!("Gamepad disconnected: {}", gilrs.gamepad(id).name());
+                    // its releases will not arrive anymore
+                    for button in GamepadButton::ALL {
+                        on_button(button, false);
+                    }
+                    if self.active == Some(id) {
+                        self.active = None;
+                    }
+                    continue;
+                }
+                EventType::ButtonPressed(button, _) => {
+                    if let Some(button) = gamepad_button(button) {
+                        on_button(button, true);
+                    }
+                }
+                EventType::ButtonReleased(button, _) => {
+                    if let Some(button) = gamepad_button(button) {
+                        on_button(button, false);
+                    }
+                }
+                _ => {}
+            }
+            self.active = Some(id);
+        }
+
+        let Some(gamepad) = self.active.and_then(|id| gilrs.connected_gamepad(id)) else {
+            return GamepadAxes::default();
+        };
+        let trigger = |button| gamepad.button_data(button).map_or(0.0, |button_data| button_data.value());
+        GamepadAxes {
+            left_stick: Vec2::new(gamepad.value(Axis::LeftStickX), gamepad.value(Axis::LeftStickY)),
+            right_stick: Vec2::new(gamepad.value(Axis::RightStickX), gamepad.value(Axis::RightStickY)),
+            left_trigger: trigger(Button::LeftTrigger2),
+            right_trigger: trigger(Button::RightTrigger2),
+        }
+    }
+}
+
+// None for the analog triggers, which are read as axes, and for buttons few gamepads have
+fn gamepad_button(button: Button) -> Option<GamepadButton> {
+    Some(match button {
+        Button::South => GamepadButton::South,
+        Button::East => GamepadButton::East,
+        Button::North => GamepadButton::North,
+        Button::West => GamepadButton::West,
+        Button::LeftTrigger => GamepadButton::LeftBumper,
+        Button::RightTrigger => GamepadButton::RightBumper,
+        Button::Select => GamepadButton::Select,
+        Button::Start => GamepadButton::Start,
+        Button::LeftThumb => GamepadButton::LeftStick,
+        Button::RightThumb => GamepadButton::RightStick,
+        Button::DPadUp => GamepadButton::DPadUp,
+        Button::DPadDown => GamepadButton::DPadDown,
+        Button::DPadLeft => GamepadButton::DPadLeft,
+        Button::DPadRight => GamepadButton::DPadRight,
+        _ => return None,
+    })
+}
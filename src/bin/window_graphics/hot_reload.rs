@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use log::{info, warn};
+use shaderc::ShaderKind;
+use vulkan_playground::{load_shader_module, ShaderCompiler};
+use crate::App;
+use crate::shader_modules::ShadingMode;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+// watches the window_graphics shader sources and the shared includes, and rebuilds every pipeline when one of
+// them changes; the embedded shader! modules are only used for the first pipelines
+pub struct ShaderHotReload {
+    compiler: Option<ShaderCompiler>,
+    watched_dirs: Vec<PathBuf>,
+    modified_times: Vec<(PathBuf, Option<SystemTime>)>,
+    last_poll: Instant,
+    // compiler output of the last failed reload, the previous pipelines stay in use meanwhile
+    pub error: Option<String>,
+}
+
+impl ShaderHotReload {
+    pub fn new() -> Self {
+        let compiler = ShaderCompiler::new()
+            .inspect_err(|error| warn!("Shader hot reload disabled: {error}"))
+            .ok();
+
+        let watched_dirs = vec![
+            vulkan_playground::assets::shader_path("window_graphics"),
+            vulkan_playground::assets::shader_path("include"),
+        ];
+        let modified_times = modified_times(&watched_dirs);
+
+        ShaderHotReload {
+            compiler,
+            watched_dirs,
+            modified_times,
+            last_poll: Instant::now(),
+            error: None,
+        }
+    }
+
+    fn poll_changed(&mut self) -> bool {
+        if self.compiler.is_none() || self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+
+        let modified_times = modified_times(&self.watched_dirs);
+        if modified_times == self.modified_times {
+            return false;
+        }
+        self.modified_times = modified_times;
+        self.compiler.as_mut().unwrap().clear_cache();
+        true
+    }
+}
+
+// sorted by path, so added and removed files count as changes too
+fn modified_times(dirs: &[PathBuf]) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut modified_times = dirs.iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| {
+            let path = entry.path();
+            let modified_time = modified_time(&path);
+            (path, modified_time)
+        })
+        .collect::<Vec<_>>();
+    modified_times.sort();
+    modified_times
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+impl App {
+    pub fn reload_shaders_if_changed(&mut self) {
+        if !self.shader_hot_reload.poll_changed() {
+            return;
+        }
+
+        info!("Shader source changed, rebuilding pipelines");
+        match self.rebuild_pipelines() {
+            Ok(pipeline_creation_duration) => {
+                info!("Pipelines rebuilt");
+                self.shader_hot_reload.error = None;
+                self.frame_duration.pipeline_creation_duration = Some(pipeline_creation_duration);
+            }
+            Err(error) => {
+                warn!("Shader reload failed, keeping previous pipelines:\n{error}");
+                self.shader_hot_reload.error = Some(error);
+            }
+        }
+    }
+
+    // replaces the pipelines only when every permutation compiled, returns the time spent creating pipelines
+    // without the shader compilation
+    fn rebuild_pipelines(&mut self) -> Result<Duration, String> {
+        let compiler = self.shader_hot_reload.compiler.as_mut().unwrap();
+        let vertex_shader_path = vulkan_playground::assets::shader_path("window_graphics/shader.vert");
+        let fragment_shader_path = vulkan_playground::assets::shader_path("window_graphics/shader.frag");
+
+        let vertex_spirv = compiler.compile_file(&vertex_shader_path, ShaderKind::Vertex, &[])?;
+        let fragment_spirvs = ShadingMode::ALL.into_iter()
+            .map(|shading_mode| {
+                compiler.compile_file(&fragment_shader_path, ShaderKind::Fragment, shading_mode.fragment_defines())
+                    .map(|spirv| (shading_mode, spirv))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let device = self.vulkan_items.device.clone();
+        let color_format = self.render_context.as_ref().unwrap().swapchain.image_format();
+        let depth_format = self.render_context.as_ref().unwrap().depth_format;
+
+        let vertex_shader = load_shader_module(device.clone(), &vertex_spirv)
+            .map_err(|error| format!("Failed to create vertex shader: {error}"))?
+            .entry_point("main")
+            .ok_or("Vertex shader has no main entry point")?;
+
+        let fragment_shaders = fragment_spirvs.into_iter()
+            .map(|(shading_mode, fragment_spirv)| {
+                load_shader_module(device.clone(), &fragment_spirv)
+                    .map_err(|error| format!("Failed to create {} fragment shader: {error}", shading_mode.name()))?
+                    .entry_point("main")
+                    .map(|fragment_shader| (shading_mode, fragment_shader))
+                    .ok_or_else(|| "Fragment shader has no main entry point".to_string())
+            })
+            .collect::<Result<HashMap<_, _>, String>>()?;
+
+        let pipeline_creation_start = Instant::now();
+        let pipelines = self.create_pipelines(&vertex_shader, &fragment_shaders, color_format, depth_format)?;
+        let render_context = self.render_context.as_mut().unwrap();
+        render_context.pipelines = pipelines;
+        render_context.vertex_shader = vertex_shader;
+        render_context.fragment_shaders = fragment_shaders;
+        Ok(pipeline_creation_start.elapsed())
+    }
+}
@@ -0,0 +1,361 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use winit::event::MouseButton;
+use winit::keyboard::KeyCode;
+
+// what the logic asks for, independent of the inputs it is bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    ToggleFrameTimes,
+    ToggleUi,
+    ToggleCameraMode,
+    FrameMesh,
+    OrbitLeft,
+    OrbitRight,
+    OrbitUp,
+    OrbitDown,
+    ZoomIn,
+    ZoomOut,
+    // drags, only mouse buttons start them
+    OrbitDrag,
+    PanDrag,
+    LookDrag,
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    MoveFaster,
+    MoveSlower,
+    LookLeft,
+    LookRight,
+    LookUp,
+    LookDown,
+}
+
+impl Action {
+    pub const ALL: [Action; 25] = [
+        Action::ToggleFrameTimes, Action::ToggleUi, Action::ToggleCameraMode, Action::FrameMesh,
+        Action::OrbitLeft, Action::OrbitRight, Action::OrbitUp, Action::OrbitDown, Action::ZoomIn, Action::ZoomOut,
+        Action::OrbitDrag, Action::PanDrag, Action::LookDrag,
+        Action::MoveForward, Action::MoveBack, Action::MoveLeft, Action::MoveRight, Action::MoveUp, Action::MoveDown,
+        Action::MoveFaster, Action::MoveSlower,
+        Action::LookLeft, Action::LookRight, Action::LookUp, Action::LookDown,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::ToggleFrameTimes => "Toggle frame times",
+            Action::ToggleUi => "Toggle windows",
+            Action::ToggleCameraMode => "Switch orbit and fly",
+            Action::FrameMesh => "Frame mesh",
+            Action::OrbitLeft => "Orbit left",
+            Action::OrbitRight => "Orbit right",
+            Action::OrbitUp => "Orbit up",
+            Action::OrbitDown => "Orbit down",
+            Action::ZoomIn => "Zoom in",
+            Action::ZoomOut => "Zoom out",
+            Action::OrbitDrag => "Orbit (drag)",
+            Action::PanDrag => "Pan (drag)",
+            Action::LookDrag => "Look around (drag)",
+            Action::MoveForward => "Fly forward",
+            Action::MoveBack => "Fly back",
+            Action::MoveLeft => "Fly left",
+            Action::MoveRight => "Fly right",
+            Action::MoveUp => "Fly up",
+            Action::MoveDown => "Fly down",
+            Action::MoveFaster => "Fly faster",
+            Action::MoveSlower => "Fly slower",
+            Action::LookLeft => "Look left",
+            Action::LookRight => "Look right",
+            Action::LookUp => "Look up",
+            Action::LookDown => "Look down",
+        }
+    }
+
+    fn default_bindings(self) -> Vec<Binding> {
+        use KeyCode::*;
+        use GamepadButton as Pad;
+        let (key_codes, gamepad_buttons): (&[KeyCode], &[GamepadButton]) = match self {
+            Action::ToggleFrameTimes => (&[KeyT], &[Pad::Select]),
+            Action::ToggleUi => (&[KeyH], &[Pad::Start]),
+            Action::ToggleCameraMode => (&[KeyF], &[Pad::North]),
+            Action::FrameMesh => (&[Home], &[Pad::West]),
+            Action::OrbitLeft | Action::LookLeft => (&[ArrowLeft], &[Pad::DPadLeft]),
+            Action::OrbitRight | Action::LookRight => (&[ArrowRight], &[Pad::DPadRight]),
+            Action::OrbitUp | Action::LookUp => (&[ArrowUp], &[Pad::DPadUp]),
+            Action::OrbitDown | Action::LookDown => (&[ArrowDown], &[Pad::DPadDown]),
+            Action::ZoomIn => (&[PageUp], &[Pad::RightBumper]),
+            Action::ZoomOut => (&[PageDown], &[Pad::LeftBumper]),
+            Action::OrbitDrag => return vec![Binding::Mouse(MouseButton::Left)],
+            Action::PanDrag => return vec![Binding::Mouse(MouseButton::Middle)],
+            Action::LookDrag => return vec![Binding::Mouse(MouseButton::Left), Binding::Mouse(MouseButton::Right)],
+            Action::MoveForward => (&[KeyW], &[]),
+            Action::MoveBack => (&[KeyS], &[]),
+            Action::MoveLeft => (&[KeyA], &[]),
+            Action::MoveRight => (&[KeyD], &[]),
+            Action::MoveUp => (&[KeyE], &[Pad::RightBumper]),
+            Action::MoveDown => (&[KeyQ], &[Pad::LeftBumper]),
+            Action::MoveFaster => (&[ShiftLeft, ShiftRight], &[Pad::LeftStick]),
+            Action::MoveSlower => (&[ControlLeft, ControlRight], &[Pad::RightStick]),
+        };
+
+        key_codes.iter().map(|&key_code| Binding::Key(key_code))
+            .chain(gamepad_buttons.iter().map(|&button| Binding::Gamepad(button)))
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+impl GamepadButton {
+    pub const ALL: [GamepadButton; 14] = [
+        GamepadButton::South, GamepadButton::East, GamepadButton::North, GamepadButton::West,
+        GamepadButton::LeftBumper, GamepadButton::RightBumper, GamepadButton::Select, GamepadButton::Start,
+        GamepadButton::LeftStick, GamepadButton::RightStick,
+        GamepadButton::DPadUp, GamepadButton::DPadDown, GamepadButton::DPadLeft, GamepadButton::DPadRight,
+    ];
+}
+
+// a single input an action can be bound to, written like KeyT, MouseLeft or GamepadSouth in the bindings file
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "String", try_from = "String")]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+
+// the keys bindings can name, the names are those of winit
+const KEYS: [KeyCode; 82] = {
+    use KeyCode::*;
+    [
+        KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL, KeyM,
+        KeyN, KeyO, KeyP, KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ,
+        Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9,
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+        ArrowLeft, ArrowRight, ArrowUp, ArrowDown, PageUp, PageDown, Home, End, Insert, Delete,
+        Space, Enter, Escape, Tab, Backspace,
+        ShiftLeft, ShiftRight, ControlLeft, ControlRight, AltLeft, AltRight,
+        Minus, Equal, BracketLeft, BracketRight, Semicolon, Quote, Backquote, Backslash, Comma, Period, Slash,
+        NumpadAdd, NumpadSubtract,
+    ]
+};
+
+const MOUSE_BUTTONS: [MouseButton; 5] = [
+    MouseButton::Left, MouseButton::Right, MouseButton::Middle, MouseButton::Back, MouseButton::Forward,
+];
+
+impl Binding {
+    // whether the bindings file can name it
+    pub fn is_nameable(self) -> bool {
+        match self {
+            Binding::Key(key_code) => KEYS.contains(&key_code),
+            Binding::Mouse(button) => MOUSE_BUTTONS.contains(&button),
+            Binding::Gamepad(_) => true,
+        }
+    }
+}
+
+impl Display for Binding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Binding::Key(key_code) => write!(f, "{key_code:?}"),
+            Binding::Mouse(button) => write!(f, "Mouse{button:?}"),
+            Binding::Gamepad(button) => write!(f, "Gamepad{button:?}"),
+        }
+    }
+}
+
+impl FromStr for Binding {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        KEYS.into_iter().map(Binding::Key)
+            .chain(MOUSE_BUTTONS.into_iter().map(Binding::Mouse))
+            .chain(GamepadButton::ALL.into_iter().map(Binding::Gamepad))
+            .find(|binding| binding.to_string() == value)
+            .ok_or_else(|| format!("unknown input \"{value}\""))
+    }
+}
+
+impl From<Binding> for String {
+    fn from(binding: Binding) -> Self {
+        binding.to_string()
+    }
+}
+
+impl TryFrom<String> for Binding {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+// every action with the inputs that trigger it, an input can trigger several actions
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Bindings(BTreeMap<Action, Vec<Binding>>);
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings(Action::ALL.into_iter().map(|action| (action, action.default_bindings())).collect())
+    }
+}
+
+impl Bindings {
+    // in the user config dir, used unless another file is given on the command line
+    pub fn default_path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("vulkan_playground").join("input_bindings.json"))
+    }
+
+    // actions the file leaves out keep their default bindings
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(path)
+            .map_err(|error| format!("Failed to read bindings from {}: {error}", path.display()))?;
+        let loaded: Bindings = serde_json::from_str(&json)
+            .map_err(|error| format!("Invalid bindings in {}: {error}", path.display()))?;
+
+        let mut bindings = Bindings::default();
+        bindings.0.extend(loaded.0);
+        Ok(bindings)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|error| format!("Failed to create {}: {error}", dir.display()))?;
+        }
+        let json = serde_json::to_string_pretty(self).unwrap();
+        fs::write(path, json).map_err(|error| format!("Failed to write bindings to {}: {error}", path.display()))
+    }
+
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn is_bound(&self, action: Action, binding: Binding) -> bool {
+        self.get(action).contains(&binding)
+    }
+
+    pub fn add(&mut self, action: Action, binding: Binding) {
+        let bindings = self.0.entry(action).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn remove(&mut self, action: Action, binding: Binding) {
+        if let Some(bindings) = self.0.get_mut(&action) {
+            bindings.retain(|&bound| bound != binding);
+        }
+    }
+}
+
+// the inputs that are down, and the actions they trigger
+pub struct ActionInput {
+    pub bindings: Bindings,
+    // where the bindings are saved to, None when there is no config dir
+    pub bindings_path: Option<PathBuf>,
+    down: HashSet<Binding>,
+    // since the last frame
+    pressed: HashSet<Binding>,
+    // the action the next pressed input gets bound to
+    pub capturing: Option<Action>,
+    // of the last load or save, shown in the UI
+    pub bindings_error: Option<String>,
+}
+
+impl ActionInput {
+    // the default bindings when the file does not exist or fails to load
+    pub fn load(bindings_path: Option<PathBuf>) -> Self {
+        let loaded = bindings_path.as_deref()
+            .filter(|path| path.exists())
+            .map(Bindings::load)
+            .transpose();
+        let (bindings, bindings_error) = match loaded {
+            Ok(bindings) => (bindings.unwrap_or_default(), None),
+            Err(error) => {
+                warn!("{error}, using the default bindings");
+                (Bindings::default(), Some(error))
+            }
+        };
+
+        ActionInput {
+            bindings,
+            bindings_path,
+            down: HashSet::new(),
+            pressed: HashSet::new(),
+            capturing: None,
+            bindings_error,
+        }
+    }
+
+    pub fn save_bindings(&mut self) {
+        self.bindings_error = match &self.bindings_path {
+            Some(path) => self.bindings.save(path).err(),
+            None => Some("No config dir to save the bindings to, pass --bindings".to_string()),
+        };
+        if let Some(error) = &self.bindings_error {
+            warn!("{error}");
+        }
+    }
+
+    // returns false when the press was taken to bind an action instead; escape cancels binding
+    pub fn press(&mut self, binding: Binding) -> bool {
+        if let Some(action) = self.capturing.take() {
+            if binding != Binding::Key(KeyCode::Escape) && binding.is_nameable() {
+                self.bindings.add(action, binding);
+            }
+            return false;
+        }
+
+        self.pressed.insert(binding);
+        self.down.insert(binding);
+        true
+    }
+
+    pub fn release(&mut self, binding: Binding) {
+        self.down.remove(&binding);
+    }
+
+    pub fn is_down(&self, action: Action) -> bool {
+        self.bindings.get(action).iter().any(|binding| self.down.contains(binding))
+    }
+
+    pub fn was_pressed(&self, action: Action) -> bool {
+        self.bindings.get(action).iter().any(|binding| self.pressed.contains(binding))
+    }
+
+    // 1 when only the positive action is down, -1 when only the negative one is
+    pub fn axis(&self, negative: Action, positive: Action) -> f32 {
+        self.is_down(positive) as i32 as f32 - self.is_down(negative) as i32 as f32
+    }
+
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+    }
+}
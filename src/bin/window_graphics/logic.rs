@@ -1,27 +1,152 @@
 use std::f32::consts::FRAC_PI_2;
-use std::time::Instant;
-use glam::{Mat4, Vec3};
+use std::time::{Duration, Instant};
+use glam::{Mat4, Vec2, Vec3};
+use log::warn;
 use vulkano::sync::GpuFuture;
-use winit::event::{KeyEvent};
-use winit::keyboard::{PhysicalKey};
-use winit::keyboard::KeyCode::{ArrowDown, ArrowLeft, ArrowRight, ArrowUp, KeyT, PageDown, PageUp};
-use crate::{App};
+use winit::event::{KeyEvent, MouseButton, MouseScrollDelta};
+use winit::keyboard::PhysicalKey;
+use winit::window::CursorGrabMode;
+use vulkan_playground::TimestampQueries;
+use vulkan_playground::scene::{CameraInstance, SceneCamera};
+use crate::{App, MouseDrag};
+use crate::camera_controller::CameraMode;
+use crate::input_actions::{Action, Binding};
+use crate::scene_buffers::SceneBuffers;
 use crate::shader_modules::{fragment_shader_module, vertex_shader_module};
 
+// radians per pixel dragged
+const ORBIT_SPEED: f32 = 0.005;
+// distance factor per line scrolled
+const ZOOM_STEP: f32 = 0.9;
+// touchpads scroll in pixels
+const PIXELS_PER_LINE: f32 = 40.0;
+const DOUBLE_CLICK_TIME: Duration = Duration::from_millis(400);
+const DOUBLE_CLICK_DISTANCE: f32 = 4.0;
+// radians per unit of raw mouse motion
+const LOOK_SPEED: f32 = 0.003;
+// fly speed factor per line scrolled
+const FLY_SPEED_STEP: f32 = 1.2;
+// while the faster or slower action is held
+const FLY_FAST_FACTOR: f32 = 4.0;
+const FLY_SLOW_FACTOR: f32 = 0.25;
+// viewport heights per second with the right stick fully tilted
+const GAMEPAD_PAN_SPEED: f32 = 0.5;
+// lines scrolled per second with a trigger fully pressed
+const GAMEPAD_ZOOM_SPEED: f32 = 4.0;
+
 impl App {
 
+    pub fn process_cursor_moved(&mut self, position: Vec2) {
+        let mouse = &mut self.logic_items.mouse;
+        if let (Some(previous_position), Some(_)) = (mouse.cursor_position, mouse.drag) {
+            mouse.drag_offset += position - previous_position;
+        }
+        mouse.cursor_position = Some(position);
+    }
+
+    // raw device motion, which keeps coming while the cursor is locked in place
+    pub fn process_mouse_motion(&mut self, delta: Vec2) {
+        let mouse = &mut self.logic_items.mouse;
+        if let Some((_, MouseDrag::Look)) = mouse.drag {
+            mouse.look_offset += delta;
+        }
+    }
+
+    // only called for presses egui did not take
+    pub fn process_mouse_press(&mut self, button: MouseButton) {
+        let binding = Binding::Mouse(button);
+        if !self.logic_items.input.press(binding) {
+            return;
+        }
+
+        let bindings = &self.logic_items.input.bindings;
+        let mouse = &mut self.logic_items.mouse;
+        let Some(cursor_position) = mouse.cursor_position else {
+            return;
+        };
+        if mouse.drag.is_some() || self.logic_items.scene_camera.is_some() {
+            return;
+        }
+
+        let drag = match self.logic_items.camera.controller.mode() {
+            CameraMode::Orbit if bindings.is_bound(Action::OrbitDrag, binding) => {
+                let now = Instant::now();
+                let double_click = mouse.last_click.is_some_and(|(time, position)| {
+                    now - time < DOUBLE_CLICK_TIME && position.distance(cursor_position) < DOUBLE_CLICK_DISTANCE
+                });
+                if double_click {
+                    mouse.double_click = Some(cursor_position);
+                    mouse.last_click = None;
+                } else {
+                    mouse.last_click = Some((now, cursor_position));
+                }
+                MouseDrag::Orbit
+            }
+            CameraMode::Orbit if bindings.is_bound(Action::PanDrag, binding) => MouseDrag::Pan,
+            CameraMode::Fly if bindings.is_bound(Action::LookDrag, binding) => MouseDrag::Look,
+            _ => return,
+        };
+
+        mouse.drag = Some((button, drag));
+        if drag == MouseDrag::Look {
+            self.set_cursor_grab(true);
+        }
+    }
+
+    pub fn process_mouse_release(&mut self, button: MouseButton) {
+        self.logic_items.input.release(Binding::Mouse(button));
+        if self.logic_items.mouse.drag.is_some_and(|(drag_button, _)| drag_button == button) {
+            self.end_drag();
+        }
+    }
+
+    pub fn end_drag(&mut self) {
+        if let Some((_, MouseDrag::Look)) = self.logic_items.mouse.drag.take() {
+            self.set_cursor_grab(false);
+        }
+    }
+
+    // locked keeps the cursor in place, platforms without locking confine it to the window instead
+    fn set_cursor_grab(&self, grab: bool) {
+        let window = &self.render_context.as_ref().unwrap().window;
+        let result = if grab {
+            window.set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined))
+        } else {
+            window.set_cursor_grab(CursorGrabMode::None)
+        };
+        if let Err(error) = result {
+            warn!("Failed to change the cursor grab: {error}");
+        }
+        window.set_cursor_visible(!grab);
+    }
+
+    // only called for scrolling egui did not take
+    pub fn process_mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        self.logic_items.mouse.scroll_lines += match delta {
+            MouseScrollDelta::LineDelta(_, lines) => lines,
+            MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE,
+        };
+    }
+
+    // keeps the pose of the current camera and takes over from a scene camera
+    pub fn set_camera_mode(&mut self, mode: CameraMode) {
+        self.end_drag();
+        self.logic_items.camera.controller.set_mode(mode);
+        self.logic_items.scene_camera = None;
+    }
+
     pub fn process_keyboard_input(&mut self, event: KeyEvent) {
-        if event.repeat == true {
+        if event.repeat {
             return;
         }
 
         match event.physical_key {
             PhysicalKey::Code(key_code) => {
                 if event.state.is_pressed() {
-                    self.logic_items.keys_pressed.insert(key_code);
-                    self.logic_items.keys_down.insert(key_code);
+                    self.logic_items.input.press(Binding::Key(key_code));
                 } else {
-                    self.logic_items.keys_down.remove(&key_code);
+                    self.logic_items.input.release(Binding::Key(key_code));
                 }
             }
             PhysicalKey::Unidentified(_) => {}
@@ -29,41 +154,131 @@ impl App {
     }
 
     fn handle_input(&mut self, frame_duration: f32) {
-        let keys_pressed = &self.logic_items.keys_pressed;
-        let keys_down = &self.logic_items.keys_down;
+        let input = &mut self.logic_items.input;
+        self.logic_items.gamepad = self.gamepads.poll(|button, pressed| {
+            if pressed {
+                input.press(Binding::Gamepad(button));
+            } else {
+                input.release(Binding::Gamepad(button));
+            }
+        });
+        let input = &self.logic_items.input;
 
-        if keys_pressed.contains(&KeyT) {
+        if input.was_pressed(Action::ToggleFrameTimes) {
             self.logic_items.show_frame_times = !self.logic_items.show_frame_times;
         }
+        if input.was_pressed(Action::ToggleUi) {
+            self.logic_items.show_ui = !self.logic_items.show_ui;
+        }
+        if input.was_pressed(Action::FrameMesh) {
+            self.logic_items.frame_scene = true;
+        }
+        if input.was_pressed(Action::ToggleCameraMode) {
+            self.set_camera_mode(self.logic_items.camera.controller.mode().toggled());
+        }
+        if self.logic_items.frame_scene {
+            self.frame_scene();
+        }
+
+        // scene cameras are fixed
+        if self.logic_items.scene_camera.is_some() {
+            return;
+        }
+
+        match self.logic_items.camera.controller.mode() {
+            CameraMode::Orbit => self.handle_orbit_input(frame_duration),
+            CameraMode::Fly => self.handle_fly_input(frame_duration),
+        }
+    }
+
+    // fits the whole scene in the view of the camera the user moves
+    fn frame_scene(&mut self) {
+        self.logic_items.frame_scene = false;
+        let Some((center, radius)) = self.scene_buffers.as_ref().and_then(SceneBuffers::bounding_sphere) else {
+            return;
+        };
+        let aspect_ratio = self.aspect_ratio();
+        self.logic_items.camera.frame_sphere(center, radius, aspect_ratio);
+    }
+
+    fn handle_orbit_input(&mut self, frame_duration: f32) {
+        let input = &self.logic_items.input;
+        let gamepad = self.logic_items.gamepad;
 
         // camera controls
         // rotate 90 degrees (pi/2) in 1 sec
         // zoom 1m in 1 sec
+        // the left stick orbits at the same speed when fully tilted
 
-        let mut vertical_angle_diff = FRAC_PI_2 * frame_duration;
-        let mut horizontal_angle_diff = FRAC_PI_2 * frame_duration;
-        if keys_down.contains(&ArrowDown) {
-            vertical_angle_diff *= -1.0;
-        }
-        if keys_down.contains(&ArrowLeft) {
-            horizontal_angle_diff *= -1.0;
-        }
+        let angle_diff = FRAC_PI_2 * frame_duration;
+        let orbit_camera = &mut self.logic_items.camera.controller.orbit;
+        orbit_camera.orbit((input.axis(Action::OrbitLeft, Action::OrbitRight) + gamepad.left_stick.x) * angle_diff,
+                           (input.axis(Action::OrbitDown, Action::OrbitUp) + gamepad.left_stick.y) * angle_diff);
+        orbit_camera.dolly(input.axis(Action::ZoomOut, Action::ZoomIn) * frame_duration);
 
-        if keys_down.contains(&ArrowUp) || keys_down.contains(&ArrowDown) {
-            self.logic_items.eye_pos = self.logic_items.eye_pos.rotate_axis(self.logic_items.eye_horizon, vertical_angle_diff);
-        }
-        if keys_down.contains(&ArrowLeft) || keys_down.contains(&ArrowRight) {
-            self.logic_items.eye_pos = self.logic_items.eye_pos.rotate_y(horizontal_angle_diff);
-            self.logic_items.eye_horizon = self.logic_items.eye_horizon.rotate_y(horizontal_angle_diff);
+        let viewport_height = self.render_context.as_ref().unwrap().viewport.extent[1];
+        let fov_y = self.logic_items.camera.fov_y;
+        let mouse = &self.logic_items.mouse;
+        match mouse.drag {
+            Some((_, MouseDrag::Orbit)) => orbit_camera.orbit(-mouse.drag_offset.x * ORBIT_SPEED, mouse.drag_offset.y * ORBIT_SPEED),
+            Some((_, MouseDrag::Pan)) => orbit_camera.pan(mouse.drag_offset, viewport_height, fov_y),
+            _ => {}
         }
+        // the stick moves the camera, so the mesh moves the other way
+        let stick_offset = Vec2::new(-gamepad.right_stick.x, gamepad.right_stick.y) * viewport_height * GAMEPAD_PAN_SPEED * frame_duration;
+        orbit_camera.pan(stick_offset, viewport_height, fov_y);
+        let trigger_lines = (gamepad.right_trigger - gamepad.left_trigger) * GAMEPAD_ZOOM_SPEED * frame_duration;
+        orbit_camera.zoom(ZOOM_STEP.powf(mouse.scroll_lines + trigger_lines));
 
-        let mut distance_diff = 1.0 * frame_duration;
-        if keys_down.contains(&PageDown) {
-            distance_diff *= -1.0;
+        if let Some(cursor_position) = mouse.double_click {
+            self.recenter_on_surface(cursor_position);
         }
+    }
+
+    fn handle_fly_input(&mut self, frame_duration: f32) {
+        let input = &self.logic_items.input;
+        let mouse = &self.logic_items.mouse;
+        let gamepad = self.logic_items.gamepad;
+
+        // the left stick flies like the keys, the triggers go down and up
+        let movement = Vec3::new(
+            input.axis(Action::MoveLeft, Action::MoveRight) + gamepad.left_stick.x,
+            input.axis(Action::MoveDown, Action::MoveUp) + gamepad.right_trigger - gamepad.left_trigger,
+            input.axis(Action::MoveBack, Action::MoveForward) + gamepad.left_stick.y,
+        );
+        let speed_factor = if input.is_down(Action::MoveFaster) {
+            FLY_FAST_FACTOR
+        } else if input.is_down(Action::MoveSlower) {
+            FLY_SLOW_FACTOR
+        } else {
+            1.0
+        };
+        let angle_diff = FRAC_PI_2 * frame_duration;
 
-        if keys_down.contains(&PageUp) || keys_down.contains(&PageDown) {
-            self.logic_items.eye_pos += (Vec3::ZERO - self.logic_items.eye_pos).normalize() * distance_diff;
+        let fly_camera = &mut self.logic_items.camera.controller.fly;
+        fly_camera.turn((input.axis(Action::LookLeft, Action::LookRight) + gamepad.right_stick.x) * angle_diff + mouse.look_offset.x * LOOK_SPEED,
+                        (input.axis(Action::LookDown, Action::LookUp) + gamepad.right_stick.y) * angle_diff - mouse.look_offset.y * LOOK_SPEED);
+        fly_camera.scale_speed(FLY_SPEED_STEP.powf(mouse.scroll_lines));
+        fly_camera.fly(movement, speed_factor, frame_duration);
+    }
+
+    // makes the surface point under the cursor the orbit pivot, nothing happens when the cursor is over the background
+    fn recenter_on_surface(&mut self, cursor_position: Vec2) {
+        let Some(scene_buffers) = self.scene_buffers.as_ref() else {
+            return;
+        };
+        let viewport_extent = Vec2::from_array(self.render_context.as_ref().unwrap().viewport.extent);
+
+        // the cursor in normalized device coordinates, at the near and far plane
+        let ndc = cursor_position / viewport_extent * 2.0 - 1.0;
+        let inverse_view_projection = self.logic_items.camera.view_projection(self.aspect_ratio()).inverse();
+        let (near_depth, far_depth) = self.logic_items.camera.near_far_depth();
+        let near = inverse_view_projection.project_point3(ndc.extend(near_depth));
+        let far = inverse_view_projection.project_point3(ndc.extend(far_depth));
+
+        if let Some(pivot) = scene_buffers.pick(near, far - near) {
+            let orbit_camera = &mut self.logic_items.camera.controller.orbit;
+            orbit_camera.recenter(pivot, orbit_camera.eye_pos());
         }
     }
 
@@ -81,7 +296,8 @@ impl App {
         let now = Instant::now();
         let duration_since_last_start = now.duration_since(*frame_start_moments.back().unwrap());
 
-        let previous_frame_render_end = &self.render_context.as_ref().unwrap().previous_frame_render_end;
+        let render_context = self.render_context.as_ref().unwrap();
+        let previous_frame_render_end = &render_context.previous_frame_render_end;
         // let previous_frame_logic_end = self.logic_items.previous_frame_logic_end;
 
         let render_done = previous_frame_render_end.is_none()
@@ -89,7 +305,16 @@ impl App {
         // let logic_done = previous_frame_logic_end.is_some() && previous_frame_logic_end.unwrap();
 
         if render_done && self.frame_duration.render_gpu_duration.is_none() {
-            self.frame_duration.render_gpu_duration = Some();
+            let gpu_pass_durations = render_context.previous_frame_image_index
+                .and_then(|image_index| render_context.timestamp_queries.get(image_index as usize))
+                .and_then(TimestampQueries::try_results)
+                // a frame without scopes was not timed
+                .filter(|gpu_pass_durations| !gpu_pass_durations.is_empty());
+
+            if let Some(gpu_pass_durations) = gpu_pass_durations {
+                self.frame_duration.render_gpu_duration = Some(gpu_pass_durations.iter().map(|(_, duration)| *duration).sum());
+                self.frame_duration.gpu_pass_durations = gpu_pass_durations;
+            }
         }
 
         // if logic_done && self.frame_duration.logic_duration.is_none() {
@@ -108,25 +333,33 @@ impl App {
         false
     }
 
-    fn make_mvp_matrix(&self) -> Mat4 {
-        let image_extent = self.render_context.as_ref().unwrap().swapchain.image_extent();
-        let aspect_ratio = image_extent[0] as f32 / image_extent[1] as f32;
-        let projection = Mat4::perspective_lh(
-            FRAC_PI_2,
-            aspect_ratio,
-            0.1,
-            1000.0
-        );
+    // the selected scene camera, when it still exists
+    fn scene_camera(&self) -> Option<&(CameraInstance, SceneCamera)> {
+        let scene_camera = self.logic_items.scene_camera?;
+        self.scene_buffers.as_ref()?.cameras.get(scene_camera)
+    }
 
-        let view = Mat4::look_at_lh(
-            self.logic_items.eye_pos,
-            Vec3::ZERO,
-            Vec3::NEG_Y
-        );
+    fn eye_pos(&self) -> Vec3 {
+        match self.scene_camera() {
+            Some((camera_instance, _)) => camera_instance.world_transform.transform_point3(Vec3::ZERO),
+            None => self.logic_items.camera.controller.eye_pos(),
+        }
+    }
 
-        let model = Mat4::IDENTITY;
+    fn aspect_ratio(&self) -> f32 {
+        let image_extent = self.render_context.as_ref().unwrap().swapchain.image_extent();
+        image_extent[0] as f32 / image_extent[1] as f32
+    }
 
-        projection * (view * model)
+    // the model matrix of every draw is pushed separately, so this is only the view-projection
+    fn make_mvp_matrix(&self) -> Mat4 {
+        match self.scene_camera() {
+            Some((camera_instance, scene_camera)) => {
+                let projection = self.logic_items.camera.with_depth_convention(scene_camera.projection.matrix(self.aspect_ratio()));
+                projection * camera_instance.world_transform.inverse()
+            }
+            None => self.logic_items.camera.view_projection(self.aspect_ratio()),
+        }
     }
 
     pub fn frame_logic(&mut self, logic_image_index: u32) {
@@ -148,10 +381,15 @@ impl App {
         
         let fragment_data = fragment_shader_module::FragmentData {
             light_pos: self.logic_items.light_pos.to_array().into(),
-            eye_pos: self.logic_items.eye_pos.to_array(),
+            eye_pos: self.eye_pos().to_array(),
         };
         *self.logic_items.fragment_shader_uniform_buffers[logic_image_index as usize].write().unwrap() = fragment_data;
 
-        self.logic_items.keys_pressed.clear();
+        self.logic_items.input.end_frame();
+        let mouse = &mut self.logic_items.mouse;
+        mouse.drag_offset = Vec2::ZERO;
+        mouse.look_offset = Vec2::ZERO;
+        mouse.scroll_lines = 0.0;
+        mouse.double_click = None;
     }
 }
\ No newline at end of file
@@ -1,57 +1,124 @@
+mod camera;
+mod camera_controller;
+mod fly_camera;
+mod gamepad;
+mod hot_reload;
+mod input_actions;
 mod logic;
+mod orbit_camera;
 mod rendering;
+mod scene_buffers;
 mod shader_modules;
 mod ui;
 
+use std::collections::{HashMap, VecDeque};
 use std::{env, thread};
-use std::collections::{BTreeSet, VecDeque};
 use std::fmt::{Display, Formatter};
-use std::fs::File;
-use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process::exit;
 use std::sync::Arc;
-use std::sync::atomic::AtomicBool;
 use std::thread::JoinHandle;
 use std::time::{Duration, Instant};
 use egui_winit_vulkano::{Gui};
-use glam::Vec3;
+use glam::{Vec2, Vec3};
 use log::{info};
-use obj::{load_obj, Obj, Vertex};
-use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::buffer::{BufferUsage, Subbuffer};
 use vulkano::buffer::allocator::{SubbufferAllocator, SubbufferAllocatorCreateInfo};
 use vulkano::device::{DeviceExtensions, DeviceFeatures, QueueFlags};
+use vulkano::format::Format;
 use vulkano::image::view::ImageView;
-use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter};
+use vulkano::memory::allocator::{MemoryTypeFilter};
 use vulkano::pipeline::graphics::viewport::{Viewport};
 use vulkano::pipeline::{GraphicsPipeline};
+use vulkano::shader::EntryPoint;
 use vulkano::swapchain::{PresentFuture, Surface, Swapchain};
 use vulkano::sync::future::FenceSignalFuture;
 use vulkano::sync::GpuFuture;
 use winit::application::ApplicationHandler;
 use winit::dpi::PhysicalSize;
-use winit::event::{WindowEvent};
+use winit::event::{DeviceEvent, DeviceId, ElementState, MouseButton, WindowEvent};
 use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
-use winit::keyboard::{KeyCode};
+use winit::keyboard::PhysicalKey;
 use winit::window::{Window, WindowId};
-use vulkan_playground::CommonItems;
+use vulkan_playground::{CommonItems, TimestampQueries};
+use vulkan_playground::mesh::MeshOptions;
+use vulkan_playground::mesh_cache::MeshCacheMode;
+use vulkan_playground::mesh_processing::NormalMode;
+use crate::gamepad::{GamepadAxes, Gamepads};
+use crate::hot_reload::ShaderHotReload;
+use crate::input_actions::{ActionInput, Binding, Bindings};
+use crate::camera::Camera;
+use crate::camera_controller::CameraController;
+use crate::scene_buffers::SceneBuffers;
 use crate::shader_modules::vertex_shader_module::VertexData;
 use crate::shader_modules::fragment_shader_module::FragmentData;
+use crate::shader_modules::ShadingMode;
 
+const USAGE: &str = "usage: window_graphics [MESH] [--normals face|vertex|file] [--up y|z] [--scale FACTOR] [--mesh-cache next|dir|off] [--bindings FILE]";
+
+// MESH is an OBJ, glTF, GLB, PLY or STL file and defaults to the face normals bunny; more files can be dropped onto the window.
+// OBJ, PLY and STL files are cached in a binary format next to the file, or in the user cache dir with --mesh-cache dir.
+// Input bindings are read from and saved to FILE, by default input_bindings.json in the user config dir
 fn main() {
+    vulkan_playground::logging::init_logger();
+
+    let mut mesh_path = None;
+    let mut mesh_options = MeshOptions::default();
+    let mut mesh_cache_mode = MeshCacheMode::default();
+    let mut bindings_path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let parsed = match arg.as_str() {
+            "--normals" => args.next().ok_or_else(|| "--normals needs a value".to_string())
+                .and_then(|value| value.parse())
+                .map(|normals| mesh_options.normals = Some(normals)),
+            "--up" => args.next().ok_or_else(|| "--up needs a value".to_string())
+                .and_then(|value| value.parse())
+                .map(|up| mesh_options.up = up),
+            "--scale" => args.next().ok_or_else(|| "--scale needs a value".to_string())
+                .and_then(|value| value.parse().map_err(|_| format!("invalid scale \"{value}\"")))
+                .map(|scale| mesh_options.scale = scale),
+            "--mesh-cache" => args.next().ok_or_else(|| "--mesh-cache needs a value".to_string())
+                .and_then(|value| value.parse())
+                .map(|mode| mesh_cache_mode = mode),
+            "--bindings" => args.next().ok_or_else(|| "--bindings needs a value".to_string())
+                .map(|path| bindings_path = Some(PathBuf::from(path))),
+            _ if !arg.starts_with("--") && mesh_path.is_none() => {
+                mesh_path = Some(PathBuf::from(arg));
+                Ok(())
+            }
+            _ => Err(format!("unknown argument {arg}")),
+        };
+        if let Err(error) = parsed {
+            eprintln!("Invalid arguments, {error}\n{USAGE}");
+            exit(1);
+        }
+    }
+    let mesh_path = mesh_path
+        .unwrap_or_else(|| vulkan_playground::assets::resource_path("bunny_face_normals.obj"));
+
+    let bindings_path = bindings_path.or_else(Bindings::default_path);
+
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
-    let mut app = App::new(&event_loop);
+    let mut app = App::new(&event_loop, &mesh_path, mesh_options, mesh_cache_mode, bindings_path);
     event_loop.run_app(&mut app).unwrap();
 }
 
 struct App {
     vulkan_items: CommonItems,
     uniform_buffer_allocator: SubbufferAllocator,
-    vertex_buffer: Subbuffer<[Vertex]>,
-    index_buffer: Subbuffer<[u16]>,
+    // None until a scene loaded successfully
+    scene_buffers: Option<SceneBuffers>,
+    mesh_options: MeshOptions,
+    mesh_cache_mode: MeshCacheMode,
+    mesh_error: Option<String>,
     render_context: Option<RenderContext>,
     logic_items: LogicItems,
     egui: Option<Gui>,
     frame_duration: FrameDuration,
+    shader_hot_reload: ShaderHotReload,
+    gamepads: Gamepads,
     test: Arc<bool>,
 }
 
@@ -60,34 +127,82 @@ struct RenderContext {
     swapchain: Arc<Swapchain>,
     color_attachment_image_views: Vec<Arc<ImageView>>,
     depth_attachment_image_view: Arc<ImageView>,
-    pipeline: Arc<GraphicsPipeline>,
+    // chosen for what the device supports and whether reverse-Z is on
+    depth_format: Format,
+    pipelines: HashMap<ShadingMode, Arc<GraphicsPipeline>>,
+    // the shaders of the pipelines, kept to rebuild them with another depth test
+    vertex_shader: EntryPoint,
+    fragment_shaders: HashMap<ShadingMode, EntryPoint>,
     viewport: Viewport,
     recreate_swapchain: bool,
     previous_frame_render_end: Option<FenceSignalFuture<PresentFuture<Box<dyn GpuFuture>>>>,
+    previous_frame_image_index: Option<u32>,
+    // one per swapchain image, empty when the queue does not support timestamps
+    timestamp_queries: Vec<TimestampQueries>,
 }
 
 struct LogicItems {
     frame_id: i32,
     show_frame_times: bool,
+    // hides the egui windows, except for shader errors
+    show_ui: bool,
     min_frame_duration: Duration,
-    keys_pressed: BTreeSet<KeyCode>,
-    keys_down: BTreeSet<KeyCode>,
+    input: ActionInput,
+    // polled at the start of every frame
+    gamepad: GamepadAxes,
+    mouse: MouseState,
     frame_start_moments: VecDeque<Instant>,
     vertex_shader_uniform_buffers: Vec<Subbuffer<VertexData>>,
     fragment_shader_uniform_buffers: Vec<Subbuffer<FragmentData>>,
-    eye_pos: Vec3,
-    eye_horizon: Vec3,
+    camera: Camera,
+    // set when a scene loads, the camera frames it in the next frame, once the viewport size is known
+    frame_scene: bool,
     light_pos: Vec3,
+    shading_mode: ShadingMode,
+    // index into the cameras of the scene buffers, None for the camera the user moves
+    scene_camera: Option<usize>,
+    // None draws the normals the importer produced
+    normal_mode: Option<NormalMode>,
+    // kept while another normal mode is selected
+    crease_angle_degrees: f32,
     // previous_frame_logic_end: Option<bool>,
     async_duration_tracker: Option<JoinHandle<()>>,
 }
 
+// collected from window events, the camera applies it once per frame
+#[derive(Default)]
+struct MouseState {
+    // in physical pixels, None while the cursor is outside the window
+    cursor_position: Option<Vec2>,
+    // the button that started the drag ends it
+    drag: Option<(MouseButton, MouseDrag)>,
+    // since the last frame, in pixels
+    drag_offset: Vec2,
+    // raw mouse motion while looking around with the cursor grabbed, it does not move then
+    look_offset: Vec2,
+    scroll_lines: f32,
+    // where a double click happened since the last frame
+    double_click: Option<Vec2>,
+    last_click: Option<(Instant, Vec2)>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum MouseDrag {
+    Orbit,
+    Pan,
+    // fly mode
+    Look,
+}
+
 struct FrameDuration {
     logic_duration: Option<Duration>,
     ui_duration: Option<Duration>,
     render_cpu_duration: Option<Duration>,
     render_gpu_duration: Option<Duration>,
+    gpu_pass_durations: Vec<(String, Duration)>,
     frame_prep_duration: Option<Duration>,
+    // part of frame_prep_duration, only set on frames that (re)created pipelines
+    pipeline_creation_duration: Option<Duration>,
 }
 
 impl FrameDuration {
@@ -98,7 +213,9 @@ impl FrameDuration {
             ui_duration: None,
             render_cpu_duration: None,
             render_gpu_duration: None,
+            gpu_pass_durations: Vec::new(),
             frame_prep_duration: None,
+            pipeline_creation_duration: None,
         }
     }
 
@@ -112,18 +229,26 @@ impl FrameDuration {
 
 impl Display for FrameDuration {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "prep: {}, render_cpu: {}, ui: {}, logic: {}, render_gpu: {}",
-               Self::display_duration(self.frame_prep_duration),
+        write!(f, "prep: {}", Self::display_duration(self.frame_prep_duration))?;
+        if self.pipeline_creation_duration.is_some() {
+            write!(f, " (pipelines: {})", Self::display_duration(self.pipeline_creation_duration))?;
+        }
+        write!(f, ", render_cpu: {}, ui: {}, logic: {}, render_gpu: {}",
                Self::display_duration(self.render_cpu_duration),
                Self::display_duration(self.ui_duration),
                Self::display_duration(self.logic_duration),
                Self::display_duration(self.render_gpu_duration),
-        )
+        )?;
+        for (pass, duration) in &self.gpu_pass_durations {
+            write!(f, ", {}: {}", pass, Self::display_duration(Some(*duration)))?;
+        }
+        Ok(())
     }
 }
 
 impl App {
-    fn new(event_loop: &EventLoop<()>) -> Self {
+    fn new(event_loop: &EventLoop<()>, mesh_path: &Path, mesh_options: MeshOptions, mesh_cache_mode: MeshCacheMode,
+           bindings_path: Option<PathBuf>) -> Self {
         let instance_extensions = Surface::required_extensions(event_loop).unwrap();
         let device_extensions = DeviceExtensions {
             khr_swapchain: true,
@@ -135,13 +260,17 @@ impl App {
             ..DeviceFeatures::empty()
         };
 
-        let vulkan_items = vulkan_playground::get_common_vulkan_items(
-            Some(instance_extensions),
-            Some(device_extensions),
-            Some(device_features),
-            QueueFlags::GRAPHICS,
-            Some(event_loop)
-        );
+        let vulkan_items = vulkan_playground::CommonItemsBuilder::new()
+            .with_instance_extensions(instance_extensions)
+            .with_device_extensions(device_extensions)
+            .with_device_features(device_features)
+            .with_queue_flags(QueueFlags::GRAPHICS)
+            .with_presentation_support(event_loop)
+            .build()
+            .unwrap_or_else(|error| {
+                eprintln!("{error}");
+                exit(1)
+            });
 
         let uniform_buffer_allocator = SubbufferAllocator::new(
             vulkan_items.memory_allocator.clone(),
@@ -152,38 +281,6 @@ impl App {
             }
         );
 
-        let working_dir = env::current_dir().unwrap();
-        let obj_path = working_dir.join("resources/bunny_face_normals.obj");
-        info!("Reading object at {:?}", obj_path);
-        let buf_reader = BufReader::new(File::open(obj_path).unwrap());
-        let obj: Obj<Vertex, u16> = load_obj(buf_reader).unwrap();
-
-        let vertex_buffer = Buffer::from_iter(
-            vulkan_items.memory_allocator.clone(),
-            BufferCreateInfo {
-                usage: BufferUsage::VERTEX_BUFFER,
-                ..Default::default()
-            },
-            AllocationCreateInfo {
-                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-            obj.vertices
-        ).unwrap();
-
-        let index_buffer = Buffer::from_iter(
-            vulkan_items.memory_allocator.clone(),
-            BufferCreateInfo {
-                usage: BufferUsage::INDEX_BUFFER,
-                ..Default::default()
-            },
-            AllocationCreateInfo {
-                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
-                ..Default::default()
-            },
-            obj.indices
-        ).unwrap();
-
         let min_frame_duration = Duration::from_secs_f32(1.0 / 60.0);
 
         let mut frame_start_moments: VecDeque<Instant> = VecDeque::new();
@@ -194,29 +291,42 @@ impl App {
         let logic_items = LogicItems {
             frame_id: 0,
             show_frame_times: true,
+            show_ui: true,
             min_frame_duration,
-            keys_pressed: BTreeSet::new(),
-            keys_down: BTreeSet::new(),
+            input: ActionInput::load(bindings_path),
+            gamepad: GamepadAxes::default(),
+            mouse: MouseState::default(),
             frame_start_moments,
             vertex_shader_uniform_buffers: Vec::new(),
             fragment_shader_uniform_buffers: Vec::new(),
-            eye_pos: Vec3::new(0.0, 0.0, -1.5),
-            eye_horizon: Vec3::X,
+            camera: Camera::new(CameraController::new(Vec3::new(0.0, 0.0, -1.5), Vec3::ZERO)),
+            frame_scene: false,
             light_pos: Vec3::new(0.0, 10.0, 0.0),
+            shading_mode: ShadingMode::Lit,
+            scene_camera: None,
+            normal_mode: None,
+            crease_angle_degrees: NormalMode::DEFAULT_CREASE_ANGLE,
             // previous_frame_logic_end: None,
             async_duration_tracker: None,
         };
 
-        App {
+        let mut app = App {
             vulkan_items,
             uniform_buffer_allocator,
-            vertex_buffer,
-            index_buffer,
+            scene_buffers: None,
+            mesh_options,
+            mesh_cache_mode,
+            mesh_error: None,
             render_context: None,
             logic_items,
             egui: None,
             frame_duration: FrameDuration::empty(),
-        }
+            shader_hot_reload: ShaderHotReload::new(),
+            gamepads: Gamepads::new(),
+            test: Arc::new(false),
+        };
+        app.load_scene_file(mesh_path);
+        app
     }
 
     fn do_logic(&mut self, logic_image_index: u32) {
@@ -256,21 +366,67 @@ impl ApplicationHandler for App {
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _window_id: WindowId, event: WindowEvent) {
+        // while an action is being bound, the next press goes to it instead of egui or the camera
+        if self.logic_items.input.capturing.is_some() {
+            let binding = match &event {
+                WindowEvent::KeyboardInput { device_id: _, event, is_synthetic: _ } if event.state.is_pressed() && !event.repeat => {
+                    match event.physical_key {
+                        PhysicalKey::Code(key_code) => Some(Binding::Key(key_code)),
+                        PhysicalKey::Unidentified(_) => None,
+                    }
+                }
+                WindowEvent::MouseInput { device_id: _, state: ElementState::Pressed, button } => Some(Binding::Mouse(*button)),
+                _ => None,
+            };
+            if let Some(binding) = binding {
+                self.logic_items.input.press(binding);
+                return;
+            }
+        }
+
+        // tracked even when egui takes the event, so drags and keys released over a window do not get stuck
+        match &event {
+            WindowEvent::CursorMoved { device_id: _, position } => {
+                self.process_cursor_moved(Vec2::new(position.x as f32, position.y as f32));
+            }
+            WindowEvent::CursorLeft { device_id: _ } => {
+                self.logic_items.mouse.cursor_position = None;
+            }
+            // the release may never arrive, and the cursor should not stay grabbed
+            WindowEvent::Focused(false) => {
+                self.end_drag();
+            }
+            WindowEvent::MouseInput { device_id: _, state: ElementState::Released, button } => {
+                self.process_mouse_release(*button);
+            }
+            WindowEvent::KeyboardInput { device_id: _, event, is_synthetic: _ } if !event.state.is_pressed() => {
+                self.process_keyboard_input(event.clone());
+            }
+            _ => {}
+        }
+
         if self.egui.as_mut().unwrap().update(&event) {
             return;
         }
 
         match event {
             WindowEvent::CloseRequested => {
+                self.vulkan_items.save_pipeline_cache();
                 event_loop.exit();
             }
             WindowEvent::Resized(_) => {
                 self.render_context.as_mut().unwrap().recreate_swapchain = true;
             }
-            WindowEvent::MouseInput {device_id: _, state: _, button: _} => {
-
+            WindowEvent::DroppedFile(path) => {
+                self.load_scene_file(&path);
+            }
+            WindowEvent::MouseInput { device_id: _, state: ElementState::Pressed, button } => {
+                self.process_mouse_press(button);
             }
-            WindowEvent::KeyboardInput { device_id: _, event, is_synthetic: _} => {
+            WindowEvent::MouseWheel { device_id: _, delta, phase: _ } => {
+                self.process_mouse_wheel(delta);
+            }
+            WindowEvent::KeyboardInput { device_id: _, event, is_synthetic: _} if event.state.is_pressed() => {
                 self.process_keyboard_input(event);
             }
             WindowEvent::RedrawRequested => {
@@ -288,6 +444,8 @@ impl ApplicationHandler for App {
 
                 // new frame start
 
+                self.reload_shaders_if_changed();
+
                 let acquire_future = match self.frame_rendering_prep() {
                     None => return,
                     Some(result) => result,
@@ -313,6 +471,12 @@ impl ApplicationHandler for App {
         }
     }
 
+    fn device_event(&mut self, _event_loop: &ActiveEventLoop, _device_id: DeviceId, event: DeviceEvent) {
+        if let DeviceEvent::MouseMotion { delta: (x, y) } = event {
+            self.process_mouse_motion(Vec2::new(x as f32, y as f32));
+        }
+    }
+
     fn about_to_wait(&mut self, _event_loop: &ActiveEventLoop) {
         self.render_context.as_mut().unwrap().window.request_redraw();
     }
@@ -0,0 +1,88 @@
+use std::f32::consts::FRAC_PI_2;
+use glam::{Mat4, Vec2, Vec3};
+
+// looks at a pivot from a distance; yaw turns around the world Y axis, pitch tilts toward it
+pub struct OrbitCamera {
+    pub pivot: Vec3,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl OrbitCamera {
+    const MIN_DISTANCE: f32 = 0.01;
+    // just short of straight up or down, where the up vector of the view would flip
+    const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+    pub fn looking_at(pivot: Vec3, eye_pos: Vec3) -> Self {
+        let mut orbit_camera = OrbitCamera {
+            pivot,
+            distance: 1.0,
+            yaw: 0.0,
+            pitch: 0.0,
+        };
+        orbit_camera.recenter(pivot, eye_pos);
+        orbit_camera
+    }
+
+    // from the pivot toward the eye
+    fn direction(&self) -> Vec3 {
+        Vec3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        )
+    }
+
+    pub fn eye_pos(&self) -> Vec3 {
+        self.pivot + self.direction() * self.distance
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        Mat4::look_at_lh(self.eye_pos(), self.pivot, Vec3::NEG_Y)
+    }
+
+    // world space directions that point right and up on the screen
+    fn screen_axes(&self) -> (Vec3, Vec3) {
+        let forward = -self.direction();
+        let right = Vec3::NEG_Y.cross(forward).normalize();
+        (right, right.cross(forward))
+    }
+
+    // in radians, positive yaw moves the eye to the right and positive pitch moves it up
+    pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+        self.yaw += yaw;
+        self.pitch = (self.pitch + pitch).clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+    }
+
+    // moves the pivot so the point under the cursor follows a drag of offset pixels, down being positive
+    pub fn pan(&mut self, offset: Vec2, viewport_height: f32, fov_y: f32) {
+        let world_per_pixel = 2.0 * self.distance * (fov_y / 2.0).tan() / viewport_height;
+        let (right, up) = self.screen_axes();
+        self.pivot += (-right * offset.x + up * offset.y) * world_per_pixel;
+    }
+
+    // factors below 1 move closer
+    pub fn zoom(&mut self, factor: f32) {
+        self.distance = (self.distance * factor).max(Self::MIN_DISTANCE);
+    }
+
+    // positive amounts move toward the pivot, but never past it
+    pub fn dolly(&mut self, amount: f32) {
+        self.distance = (self.distance - amount).max(Self::MIN_DISTANCE);
+    }
+
+    // orbits around a new pivot, turning the camera toward it without moving the eye
+    pub fn recenter(&mut self, pivot: Vec3, eye_pos: Vec3) {
+        let offset = eye_pos - pivot;
+        let distance = offset.length();
+        if distance < Self::MIN_DISTANCE {
+            return;
+        }
+
+        self.pivot = pivot;
+        self.distance = distance;
+        self.yaw = offset.x.atan2(offset.z);
+        self.pitch = (offset.y / distance).asin().clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+    }
+}
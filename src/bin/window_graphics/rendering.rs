@@ -1,4 +1,7 @@
+use std::collections::HashMap;
+use std::error::Error;
 use std::sync::Arc;
+use std::time::Instant;
 use log::{info, warn};
 use vulkano::image::{Image, ImageCreateInfo, ImageType, ImageUsage};
 use vulkano::pipeline::{DynamicState, GraphicsPipeline, Pipeline, PipelineBindPoint, PipelineLayout, PipelineShaderStageCreateInfo};
@@ -13,18 +16,21 @@ use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
 use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
 use vulkano::swapchain::{acquire_next_image, PresentMode, Surface, Swapchain, SwapchainAcquireFuture, SwapchainCreateInfo, SwapchainPresentInfo};
 use vulkano::{Validated, VulkanError};
-use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, RenderingAttachmentInfo, RenderingInfo};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderingAttachmentInfo, RenderingInfo};
 use vulkano::descriptor_set::{DescriptorSet, WriteDescriptorSet};
-use vulkano::format::Format;
+use vulkano::format::{Format, FormatFeatures};
 use vulkano::image::view::ImageView;
 use vulkano::memory::allocator::AllocationCreateInfo;
-use vulkano::pipeline::graphics::depth_stencil::{DepthState, DepthStencilState};
+use vulkano::pipeline::graphics::depth_stencil::{CompareOp, DepthState, DepthStencilState};
 use vulkano::render_pass::{AttachmentLoadOp, AttachmentStoreOp};
+use vulkano::shader::EntryPoint;
 use vulkano::sync::GpuFuture;
 use winit::window::Window;
-use VulkanPlayground::CommonItems;
+use vulkan_playground::{CommonItems, TimestampQueries};
+use vulkan_playground::scene::SceneVertex;
 use crate::{App, RenderContext};
-use crate::shader_modules::{fragment_shader_module, vertex_shader_module};
+use crate::scene_buffers::SceneBuffers;
+use crate::shader_modules::{vertex_shader_module, ShadingMode};
 
 impl App {
     pub fn init_render_context(&mut self, window: Arc<Window>) {
@@ -51,58 +57,21 @@ impl App {
             ).unwrap()
         };
 
-        let (color_image_views, depth_image_view) = Self::make_image_views(&self.vulkan_items, &images);
+        let depth_format = self.choose_depth_format(self.logic_items.camera.reverse_z);
+        let (color_image_views, depth_image_view) = Self::make_image_views(&self.vulkan_items, &images, depth_format);
 
-        let pipeline = {
-            
-            let vertex_shader_module = vertex_shader_module::load(self.vulkan_items.device.clone()).expect("Failed to create vertex shader");
-            let fragment_shader_module = fragment_shader_module::load(self.vulkan_items.device.clone()).expect("Failed to create fragment shader");
-            let vertex_shader = vertex_shader_module.entry_point("main").unwrap();
-            let fragment_shader = fragment_shader_module.entry_point("main").unwrap();
+        let vertex_shader_module = vertex_shader_module::load(self.vulkan_items.device.clone()).expect("Failed to create vertex shader");
+        let vertex_shader = vertex_shader_module.entry_point("main").unwrap();
+        let fragment_shaders = ShadingMode::ALL.into_iter()
+            .map(|shading_mode| {
+                let fragment_shader_module = shading_mode.load_fragment_shader(self.vulkan_items.device.clone()).expect("Failed to create fragment shader");
+                (shading_mode, fragment_shader_module.entry_point("main").unwrap())
+            })
+            .collect::<HashMap<_, _>>();
 
-            let vertex_input_state = obj::Vertex::per_vertex().definition(&vertex_shader).unwrap();
-
-            let stages = [
-                PipelineShaderStageCreateInfo::new(vertex_shader),
-                PipelineShaderStageCreateInfo::new(fragment_shader)
-            ];
-
-            let layout = PipelineLayout::new(
-                self.vulkan_items.device.clone(),
-                PipelineDescriptorSetLayoutCreateInfo::from_stages(&stages)
-                    .into_pipeline_layout_create_info(self.vulkan_items.device.clone()).unwrap()
-            ).unwrap();
-
-            let dynamic_rendering_info = PipelineRenderingCreateInfo {
-                color_attachment_formats: vec![Some(swapchain.image_format())],
-                depth_attachment_format: Some(Format::D16_UNORM),
-                ..Default::default()
-            };
-
-            GraphicsPipeline::new(
-                self.vulkan_items.device.clone(),
-                None,
-                GraphicsPipelineCreateInfo {
-                    stages: stages.into_iter().collect(),
-                    vertex_input_state: Some(vertex_input_state),
-                    input_assembly_state: Some(InputAssemblyState::default()),
-                    viewport_state: Some(ViewportState::default()),
-                    rasterization_state: Some(RasterizationState::default()),
-                    depth_stencil_state: Some(DepthStencilState {
-                        depth: Some(DepthState::simple()),
-                        ..Default::default()
-                    }),
-                    multisample_state: Some(MultisampleState::default()),
-                    color_blend_state: Some(ColorBlendState::with_attachment_states(
-                        dynamic_rendering_info.color_attachment_formats.len() as u32,
-                        ColorBlendAttachmentState::default()
-                    )),
-                    dynamic_state: [DynamicState::Viewport].into_iter().collect(),
-                    subpass: Some(dynamic_rendering_info.into()),
-                    ..GraphicsPipelineCreateInfo::layout(layout.clone())
-                }
-            ).unwrap()
-        };
+        let pipeline_creation_start = Instant::now();
+        let pipelines = self.create_pipelines(&vertex_shader, &fragment_shaders, swapchain.image_format(), depth_format).unwrap();
+        self.frame_duration.pipeline_creation_duration = Some(pipeline_creation_start.elapsed());
 
         let viewport = Viewport {
             offset: [0.0, 0.0],
@@ -110,15 +79,28 @@ impl App {
             depth_range: 0.0..=1.0
         };
 
+        let timestamp_queries = (0..swapchain.image_count())
+            .filter_map(|_| TimestampQueries::new(
+                self.vulkan_items.device.clone(),
+                self.vulkan_items.queue.queue_family_index(),
+                1
+            ))
+            .collect();
+
         self.render_context = Some(RenderContext {
             window,
             swapchain,
             color_attachment_image_views: color_image_views,
             depth_attachment_image_view: depth_image_view,
-            pipeline,
+            depth_format,
+            pipelines,
+            vertex_shader,
+            fragment_shaders,
             viewport,
             recreate_swapchain: false,
             previous_frame_render_end: None,
+            previous_frame_image_index: None,
+            timestamp_queries,
         });
     }
 
@@ -144,7 +126,7 @@ impl App {
 
             render_context.swapchain = new_swapchain;
             (render_context.color_attachment_image_views,
-             render_context.depth_attachment_image_view) = Self::make_image_views(&self.vulkan_items, &new_images);
+             render_context.depth_attachment_image_view) = Self::make_image_views(&self.vulkan_items, &new_images, render_context.depth_format);
             render_context.viewport.extent = new_window_size.into();
             render_context.recreate_swapchain = false;
         }
@@ -171,15 +153,18 @@ impl App {
         let render_context = self.render_context.as_mut().unwrap();
         let image_index = acquire_future.image_index();
         let image_view = render_context.color_attachment_image_views[image_index as usize].clone();
-
-        let descriptor_set_layout = render_context.pipeline.layout().set_layouts()[0].clone();
+        let pipeline = render_context.pipelines[&self.logic_items.shading_mode].clone();
+
+        let descriptor_set_layout = pipeline.layout().set_layouts()[0].clone();
+        let descriptor_writes = [
+            WriteDescriptorSet::buffer(0, self.logic_items.vertex_shader_uniform_buffers[image_index as usize].clone()),
+            WriteDescriptorSet::buffer(1, self.logic_items.fragment_shader_uniform_buffers[image_index as usize].clone())
+        ];
+        // the layout only has the bindings the shader permutation actually uses
         let descriptor_set = DescriptorSet::new(
             self.vulkan_items.descriptor_set_allocator.clone(),
             descriptor_set_layout.clone(),
-            [
-                WriteDescriptorSet::buffer(0, self.logic_items.vertex_shader_uniform_buffers[image_index as usize].clone()),
-                WriteDescriptorSet::buffer(1, self.logic_items.fragment_shader_uniform_buffers[image_index as usize].clone())
-            ],
+            descriptor_writes.into_iter().filter(|write| descriptor_set_layout.bindings().contains_key(&write.binding())),
             []
         ).unwrap();
 
@@ -189,6 +174,15 @@ impl App {
             CommandBufferUsage::OneTimeSubmit
         ).unwrap();
 
+        // a frame that cannot be timed is still drawn
+        let timestamp_scope = render_context.timestamp_queries.get_mut(image_index as usize)
+            .and_then(|timestamp_queries| {
+                timestamp_queries.reset(&mut command_buffer_builder)
+                    .and_then(|()| timestamp_queries.begin_scope(&mut command_buffer_builder, "scene"))
+                    .inspect_err(|error| warn!("{error}"))
+                    .ok()
+            });
+
         command_buffer_builder
             .begin_rendering(
                 RenderingInfo {
@@ -201,25 +195,30 @@ impl App {
                     depth_attachment: Some(RenderingAttachmentInfo {
                         load_op: AttachmentLoadOp::Clear,
                         store_op: AttachmentStoreOp::DontCare,
-                        clear_value: Some(1f32.into()),
+                        // the far plane
+                        clear_value: Some(self.logic_items.camera.near_far_depth().1.into()),
                         ..RenderingAttachmentInfo::image_view(render_context.depth_attachment_image_view.clone())
                     }),
                     ..Default::default()
                 }
             ).unwrap()
             .set_viewport(0, [render_context.viewport.clone()].into_iter().collect()).unwrap()
-            .bind_pipeline_graphics(render_context.pipeline.clone()).unwrap()
-            .bind_descriptor_sets(PipelineBindPoint::Graphics, render_context.pipeline.layout().clone(), 0, descriptor_set).unwrap()
-            .bind_vertex_buffers(0, self.vertex_buffer.clone()).unwrap()
-            .bind_index_buffer(self.index_buffer.clone()).unwrap();
+            .bind_pipeline_graphics(pipeline.clone()).unwrap()
+            .bind_descriptor_sets(PipelineBindPoint::Graphics, pipeline.layout().clone(), 0, descriptor_set).unwrap();
 
-        unsafe {
-            command_buffer_builder.draw_indexed(self.index_buffer.len() as u32, 1, 0, 0, 0).unwrap();
+        if let Some(scene_buffers) = &self.scene_buffers {
+            Self::record_scene_draws(&self.vulkan_items, &mut command_buffer_builder, &pipeline, scene_buffers);
         }
 
         command_buffer_builder
             .end_rendering().unwrap();
 
+        if let Some(timestamp_scope) = timestamp_scope {
+            if let Err(error) = render_context.timestamp_queries[image_index as usize].end_scope(&mut command_buffer_builder, timestamp_scope) {
+                warn!("{error}");
+            }
+        }
+
         let command_buffer = command_buffer_builder.build().unwrap();
 
         let scene_future = acquire_future
@@ -234,38 +233,209 @@ impl App {
         match complete_future.map_err(Validated::unwrap) {
             Ok(future) => {
                 render_context.previous_frame_render_end = Some(future);
+                render_context.previous_frame_image_index = Some(image_index);
             }
             Err(error) => {
                 if error == VulkanError::OutOfDate {
                     render_context.recreate_swapchain = true;
                 }
                 render_context.previous_frame_render_end = None;
+                render_context.previous_frame_image_index = None;
 
                 warn!("Rendering failed: {error}");
             }
         }
     }
 
-    fn make_image_views(vulkan_items: &CommonItems, images: &[Arc<Image>]) -> (Vec<Arc<ImageView>>, Arc<ImageView>) {
+    fn record_scene_draws(vulkan_items: &CommonItems,
+                          command_buffer_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+                          pipeline: &Arc<GraphicsPipeline>,
+                          scene_buffers: &SceneBuffers
+    ) {
+        let pipeline_layout = pipeline.layout().clone();
+        // permutations that do not sample the material textures have no material set
+        let material_set_layout = pipeline_layout.set_layouts().get(1).cloned();
+
+        for mesh_instance in &scene_buffers.mesh_instances {
+            for primitive in &scene_buffers.meshes[mesh_instance.mesh] {
+                let material_bindings = &scene_buffers.materials[primitive.material];
+                let material = &material_bindings.material;
+
+                let draw_data = vertex_shader_module::DrawData {
+                    model: mesh_instance.world_transform.to_cols_array_2d(),
+                    base_color_factor: material.base_color_factor,
+                    emissive_factor: material.emissive_factor,
+                    metallic_factor: material.metallic_factor,
+                    roughness_factor: material.roughness_factor,
+                };
+                command_buffer_builder
+                    .push_constants(pipeline_layout.clone(), 0, draw_data).unwrap();
+
+                if let Some(material_set_layout) = &material_set_layout {
+                    let material_set = DescriptorSet::new(
+                        vulkan_items.descriptor_set_allocator.clone(),
+                        material_set_layout.clone(),
+                        [
+                            WriteDescriptorSet::image_view_sampler(0, material_bindings.base_color_texture.clone(), scene_buffers.sampler.clone()),
+                            WriteDescriptorSet::image_view_sampler(1, material_bindings.metallic_roughness_texture.clone(), scene_buffers.sampler.clone())
+                        ],
+                        []
+                    ).unwrap();
+                    command_buffer_builder
+                        .bind_descriptor_sets(PipelineBindPoint::Graphics, pipeline_layout.clone(), 1, material_set).unwrap();
+                }
+
+                command_buffer_builder
+                    .bind_vertex_buffers(0, primitive.vertex_buffer.clone()).unwrap()
+                    .bind_index_buffer(primitive.index_buffer.clone()).unwrap();
+
+                unsafe {
+                    command_buffer_builder.draw_indexed(primitive.index_buffer.len() as u32, 1, 0, 0, 0).unwrap();
+                }
+            }
+        }
+    }
+
+    // one pipeline per shading mode, with the depth test of the current camera
+    pub fn create_pipelines(&self,
+                            vertex_shader: &EntryPoint,
+                            fragment_shaders: &HashMap<ShadingMode, EntryPoint>,
+                            color_format: Format,
+                            depth_format: Format
+    ) -> Result<HashMap<ShadingMode, Arc<GraphicsPipeline>>, String> {
+        let depth_compare_op = if self.logic_items.camera.reverse_z { CompareOp::Greater } else { CompareOp::Less };
+        fragment_shaders.iter()
+            .map(|(&shading_mode, fragment_shader)| {
+                self.create_pipeline(vertex_shader.clone(), fragment_shader.clone(), color_format, depth_format, depth_compare_op)
+                    .map(|pipeline| (shading_mode, pipeline))
+                    .map_err(|error| format!("Failed to create {} pipeline: {error}", shading_mode.name()))
+            })
+            .collect()
+    }
+
+    // rebuilds the pipelines from the shaders they were made with, and the depth image when reverse-Z wants another
+    // format; the previous ones stay in use when this fails
+    pub fn set_reverse_z(&mut self, reverse_z: bool) {
+        self.logic_items.camera.reverse_z = reverse_z;
+        let depth_format = self.choose_depth_format(reverse_z);
+        let render_context = self.render_context.as_ref().unwrap();
+        let color_format = render_context.swapchain.image_format();
+
+        let pipeline_creation_start = Instant::now();
+        match self.create_pipelines(&render_context.vertex_shader, &render_context.fragment_shaders, color_format, depth_format) {
+            Ok(pipelines) => {
+                let render_context = self.render_context.as_mut().unwrap();
+                render_context.pipelines = pipelines;
+                if depth_format != render_context.depth_format {
+                    info!("Switching the depth format to {depth_format:?}");
+                    let extent = render_context.depth_attachment_image_view.image().extent();
+                    render_context.depth_attachment_image_view = Self::make_depth_image_view(&self.vulkan_items, extent, depth_format);
+                    render_context.depth_format = depth_format;
+                }
+                self.frame_duration.pipeline_creation_duration = Some(pipeline_creation_start.elapsed());
+            }
+            Err(error) => {
+                warn!("{error}");
+                self.logic_items.camera.reverse_z = !reverse_z;
+            }
+        }
+    }
+
+    // reverse-Z only gains precision with float depth, so it prefers D32_SFLOAT; otherwise D16_UNORM is enough.
+    // Only D16_UNORM and one of X8_D24_UNORM_PACK32 and D32_SFLOAT are guaranteed to be supported
+    fn choose_depth_format(&self, reverse_z: bool) -> Format {
+        let candidates: &[Format] = if reverse_z {
+            &[Format::D32_SFLOAT, Format::X8_D24_UNORM_PACK32, Format::D16_UNORM]
+        } else {
+            &[Format::D16_UNORM]
+        };
+        let physical_device = self.vulkan_items.device.physical_device();
+        candidates.iter().copied()
+            .find(|&format| physical_device.format_properties(format)
+                .is_ok_and(|properties| properties.optimal_tiling_features.intersects(FormatFeatures::DEPTH_STENCIL_ATTACHMENT)))
+            .unwrap_or(Format::D16_UNORM)
+    }
+
+    fn create_pipeline(&self,
+                       vertex_shader: EntryPoint,
+                       fragment_shader: EntryPoint,
+                       color_format: Format,
+                       depth_format: Format,
+                       depth_compare_op: CompareOp
+    ) -> Result<Arc<GraphicsPipeline>, Box<dyn Error>> {
+        let vertex_input_state = SceneVertex::per_vertex().definition(&vertex_shader)?;
+
+        let stages = [
+            PipelineShaderStageCreateInfo::new(vertex_shader),
+            PipelineShaderStageCreateInfo::new(fragment_shader)
+        ];
+
+        let layout = PipelineLayout::new(
+            self.vulkan_items.device.clone(),
+            PipelineDescriptorSetLayoutCreateInfo::from_stages(&stages)
+                .into_pipeline_layout_create_info(self.vulkan_items.device.clone())?
+        )?;
+
+        let dynamic_rendering_info = PipelineRenderingCreateInfo {
+            color_attachment_formats: vec![Some(color_format)],
+            depth_attachment_format: Some(depth_format),
+            ..Default::default()
+        };
+
+        let pipeline = GraphicsPipeline::new(
+            self.vulkan_items.device.clone(),
+            self.vulkan_items.pipeline_cache.clone(),
+            GraphicsPipelineCreateInfo {
+                stages: stages.into_iter().collect(),
+                vertex_input_state: Some(vertex_input_state),
+                input_assembly_state: Some(InputAssemblyState::default()),
+                viewport_state: Some(ViewportState::default()),
+                rasterization_state: Some(RasterizationState::default()),
+                depth_stencil_state: Some(DepthStencilState {
+                    depth: Some(DepthState {
+                        compare_op: depth_compare_op,
+                        ..DepthState::simple()
+                    }),
+                    ..Default::default()
+                }),
+                multisample_state: Some(MultisampleState::default()),
+                color_blend_state: Some(ColorBlendState::with_attachment_states(
+                    dynamic_rendering_info.color_attachment_formats.len() as u32,
+                    ColorBlendAttachmentState::default()
+                )),
+                dynamic_state: [DynamicState::Viewport].into_iter().collect(),
+                subpass: Some(dynamic_rendering_info.into()),
+                ..GraphicsPipelineCreateInfo::layout(layout.clone())
+            }
+        )?;
+
+        Ok(pipeline)
+    }
+
+    fn make_image_views(vulkan_items: &CommonItems, images: &[Arc<Image>], depth_format: Format) -> (Vec<Arc<ImageView>>, Arc<ImageView>) {
         let color_image_views = images.iter().map(|image| {
             ImageView::new_default(image.clone()).unwrap()
         }).collect();
 
-        let depth_image_view = ImageView::new_default(
+        let depth_image_view = Self::make_depth_image_view(vulkan_items, images[0].extent(), depth_format);
+
+        (color_image_views, depth_image_view)
+    }
+
+    fn make_depth_image_view(vulkan_items: &CommonItems, extent: [u32; 3], depth_format: Format) -> Arc<ImageView> {
+        ImageView::new_default(
             Image::new(
                 vulkan_items.memory_allocator.clone(),
                 ImageCreateInfo {
                     image_type: ImageType::Dim2d,
-                    format: Format::D16_UNORM,
-                    extent: images[0].extent(),
+                    format: depth_format,
+                    extent,
                     usage: ImageUsage::DEPTH_STENCIL_ATTACHMENT | ImageUsage::TRANSIENT_ATTACHMENT,
                     ..Default::default()
                 },
                 AllocationCreateInfo::default()
             ).unwrap()
-        ).unwrap();
-
-        (color_image_views, depth_image_view)
+        ).unwrap()
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use glam::Vec3;
+use log::{info, warn};
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, IndexBuffer, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferToImageInfo};
+use vulkano::format::Format;
+use vulkano::image::sampler::{Sampler, SamplerCreateInfo};
+use vulkano::image::view::ImageView;
+use vulkano::image::{Image, ImageCreateInfo, ImageType, ImageUsage};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter};
+use vulkano::sync;
+use vulkano::sync::GpuFuture;
+use vulkan_playground::CommonItems;
+use vulkan_playground::mesh::MeshOptions;
+use vulkan_playground::mesh_cache::{load_scene_cached, MeshCacheMode};
+use vulkan_playground::mesh_processing::{generate_normals, NormalMode};
+use vulkan_playground::scene::{Bounds, CameraInstance, Material, MeshInstance, Primitive, Scene, SceneCamera, SceneMesh, SceneVertex, Texture};
+use crate::App;
+
+// the scene that is currently drawn, replaced as a whole when another file is loaded
+pub struct SceneBuffers {
+    pub path: PathBuf,
+    // per scene mesh, one entry per primitive
+    pub meshes: Vec<Vec<PrimitiveBuffers>>,
+    pub mesh_instances: Vec<MeshInstance>,
+    // the scene materials followed by the default material, which primitives without one use
+    pub materials: Vec<MaterialBindings>,
+    pub cameras: Vec<(CameraInstance, SceneCamera)>,
+    pub sampler: Arc<Sampler>,
+    // the meshes with the normals of the file, kept to generate other normals from
+    imported_meshes: Vec<SceneMesh>,
+}
+
+pub struct PrimitiveBuffers {
+    pub vertex_buffer: Subbuffer<[SceneVertex]>,
+    // 16-bit indices when every vertex can be addressed with them, 32-bit otherwise
+    pub index_buffer: IndexBuffer,
+    pub material: usize,
+}
+
+pub struct MaterialBindings {
+    pub material: Material,
+    pub base_color_texture: Arc<ImageView>,
+    pub metallic_roughness_texture: Arc<ImageView>,
+}
+
+impl PrimitiveBuffers {
+    fn new(vulkan_items: &CommonItems, primitive: Primitive, default_material: usize) -> Result<Self, String> {
+        let vertex_count = primitive.vertices.len();
+        // at least 2^24 - 1, unless the device supports full 32-bit indices
+        let max_index = vulkan_items.device.physical_device().properties().max_draw_indexed_index_value;
+        if vertex_count > max_index as usize + 1 {
+            return Err(format!("A primitive has {} vertices, the device supports at most {}",
+                               vertex_count, max_index as usize + 1));
+        }
+
+        let vertex_buffer = create_buffer(vulkan_items, BufferUsage::VERTEX_BUFFER, primitive.vertices)
+            .map_err(|error| format!("Failed to create vertex buffer: {error}"))?;
+
+        let index_buffer = if vertex_count <= u16::MAX as usize + 1 {
+            create_buffer(vulkan_items, BufferUsage::INDEX_BUFFER, primitive.indices.into_iter().map(|index| index as u16))
+                .map(IndexBuffer::U16)
+        } else {
+            create_buffer(vulkan_items, BufferUsage::INDEX_BUFFER, primitive.indices)
+                .map(IndexBuffer::U32)
+        }.map_err(|error| format!("Failed to create index buffer: {error}"))?;
+
+        Ok(PrimitiveBuffers {
+            vertex_buffer,
+            index_buffer,
+            material: primitive.material.unwrap_or(default_material),
+        })
+    }
+}
+
+impl SceneBuffers {
+    fn new(vulkan_items: &CommonItems, path: &Path, scene: Scene, normal_mode: Option<NormalMode>) -> Result<Self, String> {
+        let mesh_instances = scene.mesh_instances();
+        let cameras = scene.camera_instances().into_iter()
+            .map(|camera_instance| (camera_instance, scene.cameras[camera_instance.camera].clone()))
+            .collect();
+
+        let materials = upload_materials(vulkan_items, scene.materials, &scene.textures)?;
+
+        let sampler = Sampler::new(vulkan_items.device.clone(), SamplerCreateInfo::simple_repeat_linear_no_mipmap())
+            .map_err(|error| format!("Failed to create sampler: {error}"))?;
+
+        let mut scene_buffers = SceneBuffers {
+            path: path.to_path_buf(),
+            meshes: Vec::new(),
+            mesh_instances,
+            materials,
+            cameras,
+            sampler,
+            imported_meshes: scene.meshes,
+        };
+        scene_buffers.upload_meshes(vulkan_items, normal_mode)?;
+        Ok(scene_buffers)
+    }
+
+    pub fn load(vulkan_items: &CommonItems, path: &Path, options: &MeshOptions, cache_mode: MeshCacheMode,
+                normal_mode: Option<NormalMode>) -> Result<Self, String> {
+        info!("Reading scene at {:?}", path);
+        let start = Instant::now();
+        let scene = load_scene_cached(path, options, cache_mode)?;
+        info!("Loaded {} meshes with {} vertices and {} triangles, {} materials and {} cameras in {:.1} ms",
+              scene.meshes.len(), scene.vertex_count(), scene.triangle_count(), scene.materials.len(), scene.cameras.len(),
+              start.elapsed().as_secs_f32() * 1000.0);
+
+        Self::new(vulkan_items, path, scene, normal_mode)
+    }
+
+    // the closest point where the ray hits a triangle of the scene, in world space
+    pub fn pick(&self, origin: Vec3, direction: Vec3) -> Option<Vec3> {
+        self.mesh_instances.iter()
+            .flat_map(|mesh_instance| {
+                // t is the same in both spaces, as the direction is transformed along without normalizing it
+                let to_local = mesh_instance.world_transform.inverse();
+                let (local_origin, local_direction) = (to_local.transform_point3(origin), to_local.transform_vector3(direction));
+                self.imported_meshes[mesh_instance.mesh].primitives.iter()
+                    .filter_map(move |primitive| primitive.ray_intersection(local_origin, local_direction))
+            })
+            .min_by(f32::total_cmp)
+            .map(|t| origin + direction * t)
+    }
+
+    // around every vertex in world space, centered on their bounds; None for a scene without vertices
+    pub fn bounding_sphere(&self) -> Option<(Vec3, f32)> {
+        let world_positions = || self.mesh_instances.iter()
+            .flat_map(|mesh_instance| {
+                self.imported_meshes[mesh_instance.mesh].primitives.iter()
+                    .flat_map(|primitive| primitive.vertices.iter())
+                    .map(|vertex| mesh_instance.world_transform.transform_point3(Vec3::from_array(vertex.position)))
+            });
+
+        let center = Bounds::from_points(world_positions())?.center();
+        let radius = world_positions().map(|position| position.distance(center)).fold(0.0, f32::max);
+        Some((center, radius))
+    }
+
+    // replaces the vertex and index buffers, with generated normals unless normal_mode is None;
+    // the previous buffers are kept when this fails
+    pub fn upload_meshes(&mut self, vulkan_items: &CommonItems, normal_mode: Option<NormalMode>) -> Result<(), String> {
+        // the default material is the last one
+        let default_material = self.materials.len() - 1;
+        let start = Instant::now();
+
+        self.meshes = self.imported_meshes.iter()
+            .map(|mesh| {
+                mesh.primitives.iter()
+                    .filter(|primitive| !primitive.indices.is_empty())
+                    .map(|primitive| {
+                        let mut primitive = primitive.clone();
+                        if let Some(normal_mode) = normal_mode {
+                            generate_normals(&mut primitive, normal_mode);
+                        }
+                        PrimitiveBuffers::new(vulkan_items, primitive, default_material)
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some(normal_mode) = normal_mode {
+            info!("Generated {} normals in {:.1} ms", normal_mode, start.elapsed().as_secs_f32() * 1000.0);
+        }
+        Ok(())
+    }
+}
+
+fn create_buffer<T, I>(vulkan_items: &CommonItems, usage: BufferUsage, content: I) -> Result<Subbuffer<[T]>, String>
+where
+    T: BufferContents,
+    I: IntoIterator<Item = T>,
+    I::IntoIter: ExactSizeIterator,
+{
+    Buffer::from_iter(
+        vulkan_items.memory_allocator.clone(),
+        BufferCreateInfo {
+            usage,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        content
+    ).map_err(|error| error.to_string())
+}
+
+// index into the scene textures and the format it is uploaded with
+type TextureKey = (Option<usize>, Format);
+
+// base color textures are sRGB and metallic-roughness textures linear, so a texture used as both is uploaded twice
+fn upload_materials(vulkan_items: &CommonItems, materials: Vec<Material>, textures: &[Texture]) -> Result<Vec<MaterialBindings>, String> {
+    let white = Texture { width: 1, height: 1, pixels: vec![255; 4] };
+    let mut uploads = HashMap::new();
+    // None stands for the white texture, also used for indices that are out of range
+    let mut texture_for = |texture: Option<usize>, format: Format| {
+        let texture = texture.filter(|&texture| texture < textures.len());
+        uploads.entry((texture, format))
+            .or_insert_with(|| texture.map_or(&white, |texture| &textures[texture]));
+        (texture, format)
+    };
+
+    let material_textures = materials.iter()
+        .map(|material| (
+            texture_for(material.base_color_texture, Format::R8G8B8A8_SRGB),
+            texture_for(material.metallic_roughness_texture, Format::R8G8B8A8_UNORM),
+        ))
+        .collect::<Vec<_>>();
+    let default_material_textures = (
+        texture_for(None, Format::R8G8B8A8_SRGB),
+        texture_for(None, Format::R8G8B8A8_UNORM),
+    );
+
+    let image_views = upload_textures(vulkan_items, uploads)?;
+
+    Ok(materials.into_iter()
+        .zip(material_textures)
+        .chain([(Material::default(), default_material_textures)])
+        .map(|(material, (base_color_texture, metallic_roughness_texture))| MaterialBindings {
+            material,
+            base_color_texture: image_views[&base_color_texture].clone(),
+            metallic_roughness_texture: image_views[&metallic_roughness_texture].clone(),
+        })
+        .collect())
+}
+
+// one submission for every texture, waited on before returning
+fn upload_textures(vulkan_items: &CommonItems, uploads: HashMap<TextureKey, &Texture>
+) -> Result<HashMap<TextureKey, Arc<ImageView>>, String> {
+    let mut command_buffer_builder = AutoCommandBufferBuilder::primary(
+        vulkan_items.command_buffer_allocator.clone(),
+        vulkan_items.queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit
+    ).map_err(|error| error.to_string())?;
+
+    let mut image_views = HashMap::new();
+    for ((texture_index, format), texture) in uploads {
+        let staging_buffer = Buffer::from_iter(
+            vulkan_items.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_SRC,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            texture.pixels.iter().copied()
+        ).map_err(|error| format!("Failed to create texture staging buffer: {error}"))?;
+
+        let image = Image::new(
+            vulkan_items.memory_allocator.clone(),
+            ImageCreateInfo {
+                image_type: ImageType::Dim2d,
+                format,
+                extent: [texture.width, texture.height, 1],
+                usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+                ..Default::default()
+            },
+            AllocationCreateInfo::default()
+        ).map_err(|error| format!("Failed to create texture image: {error}"))?;
+
+        command_buffer_builder
+            .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(staging_buffer, image.clone()))
+            .map_err(|error| error.to_string())?;
+
+        image_views.insert((texture_index, format), ImageView::new_default(image).map_err(|error| error.to_string())?);
+    }
+
+    let command_buffer = command_buffer_builder.build().map_err(|error| error.to_string())?;
+    sync::now(vulkan_items.device.clone())
+        .then_execute(vulkan_items.queue.clone(), command_buffer).map_err(|error| error.to_string())?
+        .then_signal_fence_and_flush().map_err(|error| error.to_string())?
+        .wait(None).map_err(|error| error.to_string())?;
+
+    Ok(image_views)
+}
+
+impl App {
+    // keeps drawing the previous scene when loading fails, the error is shown in the UI instead
+    pub fn load_scene_file(&mut self, path: &Path) {
+        match SceneBuffers::load(&self.vulkan_items, path, &self.mesh_options, self.mesh_cache_mode, self.logic_items.normal_mode) {
+            Ok(scene_buffers) => {
+                // frames in flight keep their own references to the old buffers
+                self.scene_buffers = Some(scene_buffers);
+                self.logic_items.scene_camera = None;
+                self.logic_items.frame_scene = true;
+                self.mesh_error = None;
+            }
+            Err(error) => {
+                warn!("{error}");
+                self.mesh_error = Some(error);
+            }
+        }
+    }
+
+    // regenerates the normals of the current scene, which keeps its previous buffers when that fails
+    pub fn set_normal_mode(&mut self, normal_mode: Option<NormalMode>) {
+        self.logic_items.normal_mode = normal_mode;
+        let Some(scene_buffers) = self.scene_buffers.as_mut() else {
+            return;
+        };
+        if let Err(error) = scene_buffers.upload_meshes(&self.vulkan_items, normal_mode) {
+            warn!("{error}");
+            self.mesh_error = Some(error);
+        }
+    }
+}
@@ -1,8 +1,15 @@
+use std::sync::Arc;
+use vulkano::device::Device;
+use vulkano::shader::ShaderModule;
+use vulkano::{Validated, VulkanError};
+use vulkan_playground::ShaderDefines;
+
 pub mod vertex_shader_module {
     vulkano_shaders::shader! {
         ty: "vertex",
         path: "shaders/window_graphics/shader.vert",
-        define: [("edit_id", "x3axd87x-xcxx-4axa-aaex-833993bdx87d")]
+        define: [("edit_id", "x3axd87x-xcxx-4axa-aaex-833993bdx87d")],
+        include: ["shaders/include"]
     }
 }
 
@@ -10,6 +17,49 @@ pub mod fragment_shader_module {
     vulkano_shaders::shader! {
         ty: "fragment",
         path: "shaders/window_graphics/shader.frag",
-        define: [("edit_id", "c9xeb1e7-93bc-4152-853b-cb5d6d4c315e")]
+        define: [("edit_id", "c9xeb1e7-93bc-4152-853b-cb5d6d4c315e")],
+        include: ["shaders/include"]
+    }
+}
+
+pub mod normals_fragment_shader_module {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "shaders/window_graphics/shader.frag",
+        define: [("SHOW_NORMALS", "1"), ("edit_id", "4bx2e6a1-7cx9-4d3x-9a1e-2x5c8b7dx3a6")],
+        include: ["shaders/include"]
+    }
+}
+
+// fragment shader permutations, each one gets its own pipeline
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ShadingMode {
+    Lit,
+    Normals,
+}
+
+impl ShadingMode {
+    pub const ALL: [ShadingMode; 2] = [ShadingMode::Lit, ShadingMode::Normals];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ShadingMode::Lit => "Lit",
+            ShadingMode::Normals => "Normals",
+        }
     }
-}
\ No newline at end of file
+
+    // the same defines as the matching shader! module above, minus edit_id, for runtime compilation
+    pub fn fragment_defines(self) -> &'static ShaderDefines {
+        match self {
+            ShadingMode::Lit => &[],
+            ShadingMode::Normals => &[("SHOW_NORMALS", "1")],
+        }
+    }
+
+    pub fn load_fragment_shader(self, device: Arc<Device>) -> Result<Arc<ShaderModule>, Validated<VulkanError>> {
+        match self {
+            ShadingMode::Lit => fragment_shader_module::load(device),
+            ShadingMode::Normals => normals_fragment_shader_module::load(device),
+        }
+    }
+}
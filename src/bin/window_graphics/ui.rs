@@ -1,7 +1,12 @@
 use egui_winit_vulkano::{Gui, GuiConfig};
 use vulkano::image::SampleCount;
 use winit::event_loop::ActiveEventLoop;
+use vulkan_playground::mesh_processing::NormalMode;
 use crate::App;
+use crate::camera::ProjectionMode;
+use crate::camera_controller::CameraMode;
+use crate::input_actions::{Action, Bindings};
+use crate::shader_modules::ShadingMode;
 
 impl App {
 
@@ -22,10 +27,187 @@ impl App {
     }
 
     pub fn build_ui(&mut self) {
+        let shader_error = self.shader_hot_reload.error.as_deref();
+        let show_ui = self.logic_items.show_ui;
+        let mut shading_mode = self.logic_items.shading_mode;
+        let mesh_path = self.scene_buffers.as_ref().map(|scene_buffers| scene_buffers.path.display().to_string());
+        let mesh_error = self.mesh_error.as_deref();
+        let camera_names = self.scene_buffers.as_ref()
+            .map(|scene_buffers| scene_buffers.cameras.iter()
+                .enumerate()
+                .map(|(index, (_, camera))| camera.name.clone().unwrap_or_else(|| format!("Camera {index}")))
+                .collect::<Vec<_>>())
+            .unwrap_or_default();
+        let mut scene_camera = self.logic_items.scene_camera;
+        let mut camera_mode = self.logic_items.camera.controller.mode();
+        let fly_speed = self.logic_items.camera.controller.fly.speed;
+        let mut projection_mode = self.logic_items.camera.projection_mode;
+        let mut fov_y_degrees = self.logic_items.camera.fov_y.to_degrees();
+        let mut near = self.logic_items.camera.near;
+        let mut far = self.logic_items.camera.far;
+        let mut reverse_z = self.logic_items.camera.reverse_z;
+        let mut frame_scene = false;
+        let mut bindings = self.logic_items.input.bindings.clone();
+        let mut capturing = self.logic_items.input.capturing;
+        let mut unbind = None;
+        let mut save_bindings = false;
+        let bindings_path = self.logic_items.input.bindings_path.as_ref().map(|path| path.display().to_string());
+        let bindings_error = self.logic_items.input.bindings_error.as_deref();
+        let mut normal_mode = self.logic_items.normal_mode;
+        let mut crease_angle_degrees = self.logic_items.crease_angle_degrees;
+
         self.egui.as_mut().unwrap().immediate_ui(|egui| {
             let egui_context = egui.context();
-            egui::Window::new("Hello world").show(&egui_context, |ui| {});
+            if show_ui {
+                egui::Window::new("Hello world").show(&egui_context, |ui| {});
+
+                egui::Window::new("Shading").show(&egui_context, |ui| {
+                    for mode in ShadingMode::ALL {
+                        ui.radio_value(&mut shading_mode, mode, mode.name());
+                    }
+                });
+
+                egui::Window::new("Mesh").show(&egui_context, |ui| {
+                    ui.label(mesh_path.as_deref().unwrap_or("No mesh loaded"));
+                    ui.label("Drop an OBJ, glTF, PLY or STL file onto the window to replace it");
+                    if let Some(mesh_error) = mesh_error {
+                        ui.colored_label(egui::Color32::RED, mesh_error);
+                    }
+
+                    ui.separator();
+                    ui.label("Normals");
+                    ui.radio_value(&mut normal_mode, None, "As imported");
+                    for mode in [NormalMode::Flat, NormalMode::AreaWeighted, NormalMode::AngleWeighted] {
+                        ui.radio_value(&mut normal_mode, Some(mode), mode.name());
+                    }
+                    let crease = matches!(normal_mode, Some(NormalMode::Crease { .. }));
+                    ui.horizontal(|ui| {
+                        let clicked = ui.radio(crease, NormalMode::Crease { angle_degrees: 0.0 }.name()).clicked();
+                        let slider = egui::Slider::new(&mut crease_angle_degrees, 0.0..=180.0).suffix("°");
+                        let slider = ui.add_enabled(crease, slider);
+                        // not applied while dragging, every change regenerates the normals of the whole scene
+                        if clicked || slider.drag_stopped() || (slider.changed() && !slider.dragged()) {
+                            normal_mode = Some(NormalMode::Crease { angle_degrees: crease_angle_degrees });
+                        }
+                    });
+                });
+
+                egui::Window::new("Camera").show(&egui_context, |ui| {
+                    for mode in CameraMode::ALL {
+                        if ui.radio(scene_camera.is_none() && camera_mode == mode, format!("{} camera", mode.name())).clicked() {
+                            scene_camera = None;
+                            camera_mode = mode;
+                        }
+                    }
+                    for (index, camera_name) in camera_names.iter().enumerate() {
+                        ui.radio_value(&mut scene_camera, Some(index), camera_name);
+                    }
+
+                    ui.separator();
+                    match camera_mode {
+                        CameraMode::Orbit => {
+                            ui.label("Scroll to zoom, double click the mesh to orbit around that point");
+                            ui.label("Gamepad: the left stick orbits, the right stick pans and the triggers zoom");
+                        }
+                        CameraMode::Fly => {
+                            ui.label(format!("Scroll to change the speed: {fly_speed:.2} m/s"));
+                            ui.label("Gamepad: the left stick flies, the right stick looks and the triggers go down and up");
+                        }
+                    }
+                    ui.label("The Input window lists the other controls");
+
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        for mode in ProjectionMode::ALL {
+                            ui.radio_value(&mut projection_mode, mode, mode.name());
+                        }
+                    });
+                    ui.add(egui::Slider::new(&mut fov_y_degrees, 10.0..=150.0).suffix("°").text("Vertical field of view"));
+                    // framing a mesh fits the planes around it, which can be outside these ranges
+                    ui.add(egui::Slider::new(&mut near, 0.0001..=100.0).logarithmic(true)
+                        .clamping(egui::SliderClamping::Never).text("Near plane"));
+                    ui.add(egui::Slider::new(&mut far, 0.1..=1000000.0).logarithmic(true)
+                        .clamping(egui::SliderClamping::Never).text("Far plane"));
+                    ui.checkbox(&mut reverse_z, "Reverse-Z");
+                    frame_scene = ui.button("Frame mesh").on_hover_text("Or press Home").clicked();
+                });
+
+                egui::Window::new("Input").default_open(false).show(&egui_context, |ui| {
+                    egui::Grid::new("bindings").striped(true).show(ui, |ui| {
+                        for action in Action::ALL {
+                            ui.label(action.name());
+                            ui.horizontal(|ui| {
+                                for &binding in bindings.get(action) {
+                                    if ui.small_button(binding.to_string()).on_hover_text("Click to unbind").clicked() {
+                                        unbind = Some((action, binding));
+                                    }
+                                }
+                                if capturing == Some(action) {
+                                    if ui.small_button("Press an input, escape cancels").clicked() {
+                                        capturing = None;
+                                    }
+                                } else if ui.small_button("+").clicked() {
+                                    capturing = Some(action);
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        save_bindings = ui.button("Save").clicked();
+                        if ui.button("Reset to defaults").clicked() {
+                            bindings = Bindings::default();
+                        }
+                    });
+                    ui.label(bindings_path.as_deref().unwrap_or("No config dir, pass --bindings to save"));
+                    if let Some(bindings_error) = bindings_error {
+                        ui.colored_label(egui::Color32::RED, bindings_error);
+                    }
+                });
+            }
+
+            if let Some(shader_error) = shader_error {
+                egui::Window::new("Shader errors").show(&egui_context, |ui| {
+                    ui.label("Reload failed, rendering with the previous pipeline");
+                    ui.separator();
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        ui.monospace(shader_error);
+                    });
+                });
+            }
         });
+
+        self.logic_items.shading_mode = shading_mode;
+        self.logic_items.scene_camera = scene_camera;
+        if camera_mode != self.logic_items.camera.controller.mode() {
+            self.set_camera_mode(camera_mode);
+        }
+        let camera = &mut self.logic_items.camera;
+        camera.projection_mode = projection_mode;
+        camera.fov_y = fov_y_degrees.to_radians();
+        camera.near = near.max(f32::MIN_POSITIVE);
+        // the projection needs some depth between the planes
+        camera.far = far.max(camera.near * 1.001);
+        if reverse_z != camera.reverse_z {
+            self.set_reverse_z(reverse_z);
+        }
+        self.logic_items.frame_scene |= frame_scene;
+
+        let input = &mut self.logic_items.input;
+        if let Some((action, binding)) = unbind {
+            bindings.remove(action, binding);
+        }
+        input.bindings = bindings;
+        input.capturing = capturing;
+        if save_bindings {
+            input.save_bindings();
+        }
+        self.logic_items.crease_angle_degrees = crease_angle_degrees;
+        if normal_mode != self.logic_items.normal_mode {
+            self.set_normal_mode(normal_mode);
+        }
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,331 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use log::warn;
+use vulkano::buffer::{AllocateBufferError, Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{DescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, Queue};
+use vulkano::image::Image;
+use vulkano::image::view::ImageView;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter, StandardMemoryAllocator};
+use vulkano::pipeline::compute::ComputePipelineCreateInfo;
+use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint, PipelineLayout, PipelineShaderStageCreateInfo};
+use vulkano::shader::ShaderModule;
+use vulkano::sync;
+use vulkano::sync::{GpuFuture, HostAccessError};
+use vulkano::{Validated, VulkanError};
+use crate::{CommonItems, TimestampQueries};
+
+// a compute pipeline with a single descriptor set (set 0), run synchronously on the compute queue
+pub struct ComputeKernel {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
+    command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+    pipeline: Arc<ComputePipeline>,
+    local_size: [u32; 3],
+    timestamp_queries: Option<Mutex<TimestampQueries>>,
+}
+
+pub enum KernelBinding {
+    Buffer(u32, Subbuffer<[u8]>),
+    Image(u32, Arc<ImageView>),
+}
+
+// the step of creating or running a kernel that failed, with the error Vulkan gave for it
+#[derive(Debug)]
+pub enum KernelError {
+    PipelineCreation(Box<dyn Error>),
+    BufferCreation(Validated<AllocateBufferError>),
+    DescriptorSetCreation(Validated<VulkanError>),
+    Recording(Box<dyn Error>),
+    Execution(Box<dyn Error>),
+    Readback(HostAccessError),
+}
+
+// number of invocations per dimension, unused dimensions are 1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Grid(pub [u32; 3]);
+
+impl KernelBinding {
+    pub fn buffer<T: ?Sized>(binding: u32, buffer: Subbuffer<T>) -> Self {
+        KernelBinding::Buffer(binding, buffer.into_bytes())
+    }
+
+    pub fn image(binding: u32, image_view: Arc<ImageView>) -> Self {
+        KernelBinding::Image(binding, image_view)
+    }
+
+    fn write(&self) -> WriteDescriptorSet {
+        match self {
+            KernelBinding::Buffer(binding, buffer) => WriteDescriptorSet::buffer(*binding, buffer.clone()),
+            KernelBinding::Image(binding, image_view) => WriteDescriptorSet::image_view(*binding, image_view.clone()),
+        }
+    }
+}
+
+impl Display for KernelError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KernelError::PipelineCreation(error) => write!(f, "Failed to create compute pipeline: {error}"),
+            KernelError::BufferCreation(error) => write!(f, "Failed to create buffer: {error}"),
+            KernelError::DescriptorSetCreation(error) => write!(f, "Failed to create descriptor set: {error}"),
+            KernelError::Recording(error) => write!(f, "Failed to record command buffer: {error}"),
+            KernelError::Execution(error) => write!(f, "Failed to execute command buffer: {error}"),
+            KernelError::Readback(error) => write!(f, "Failed to read buffer: {error}"),
+        }
+    }
+}
+
+impl Error for KernelError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            KernelError::PipelineCreation(error)
+            | KernelError::Recording(error)
+            | KernelError::Execution(error) => Some(error.as_ref()),
+            KernelError::BufferCreation(error) => Some(error),
+            KernelError::DescriptorSetCreation(error) => Some(error),
+            KernelError::Readback(error) => Some(error),
+        }
+    }
+}
+
+impl Grid {
+    // rounds up, so the shader has to skip invocations outside the grid when it is not a multiple of the local size
+    pub fn work_group_counts(&self, local_size: [u32; 3]) -> [u32; 3] {
+        [0, 1, 2].map(|i| self.0[i].div_ceil(local_size[i]))
+    }
+}
+
+impl From<u32> for Grid {
+    fn from(x: u32) -> Self {
+        Grid([x, 1, 1])
+    }
+}
+
+impl From<[u32; 1]> for Grid {
+    fn from([x]: [u32; 1]) -> Self {
+        Grid([x, 1, 1])
+    }
+}
+
+impl From<[u32; 2]> for Grid {
+    fn from([x, y]: [u32; 2]) -> Self {
+        Grid([x, y, 1])
+    }
+}
+
+impl From<[u32; 3]> for Grid {
+    fn from(size: [u32; 3]) -> Self {
+        Grid(size)
+    }
+}
+
+impl ComputeKernel {
+    // the shader declares local_size_x_id = 0, local_size_y_id = 1 and local_size_z_id = 2,
+    // which are specialized from local_size
+    pub fn new(vulkan_items: &CommonItems, shader_module: &Arc<ShaderModule>, local_size: [u32; 3]) -> Result<Self, KernelError> {
+        let device = vulkan_items.device.clone();
+
+        let entry_point = shader_module
+            .specialize((0..3).map(|id| (id, local_size[id as usize].into())).collect())
+            .map_err(|error| KernelError::PipelineCreation(error.into()))?
+            .entry_point("main")
+            .ok_or_else(|| KernelError::PipelineCreation("the shader has no main entry point".into()))?;
+        let stage = PipelineShaderStageCreateInfo::new(entry_point);
+        let pipeline_layout_create_info = PipelineDescriptorSetLayoutCreateInfo::from_stages([&stage])
+            .into_pipeline_layout_create_info(device.clone())
+            .map_err(|error| KernelError::PipelineCreation(error.into()))?;
+        let pipeline_layout = PipelineLayout::new(device.clone(), pipeline_layout_create_info)
+            .map_err(|error| KernelError::PipelineCreation(error.into()))?;
+
+        let pipeline = ComputePipeline::new(
+            device.clone(), vulkan_items.pipeline_cache.clone(),
+            ComputePipelineCreateInfo::stage_layout(stage, pipeline_layout)
+        ).map_err(|error| KernelError::PipelineCreation(error.into()))?;
+
+        let queue = vulkan_items.compute_queue.clone().unwrap_or_else(|| vulkan_items.queue.clone());
+        let timestamp_queries = TimestampQueries::new(device.clone(), queue.queue_family_index(), 1)
+            .map(Mutex::new);
+
+        Ok(ComputeKernel {
+            device,
+            queue,
+            memory_allocator: vulkan_items.memory_allocator.clone(),
+            descriptor_set_allocator: vulkan_items.descriptor_set_allocator.clone(),
+            command_buffer_allocator: vulkan_items.command_buffer_allocator.clone(),
+            pipeline,
+            local_size,
+            timestamp_queries,
+        })
+    }
+
+    pub fn local_size(&self) -> [u32; 3] {
+        self.local_size
+    }
+
+    pub fn storage_buffer_from_iter<T, I>(&self, content: I) -> Result<Subbuffer<[T]>, KernelError>
+    where
+        T: BufferContents,
+        I: IntoIterator<Item = T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        Buffer::from_iter(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            content
+        ).map_err(KernelError::BufferCreation)
+    }
+
+    // both dispatch functions return the GPU time of the dispatch, when the queue supports timestamps
+    pub fn dispatch(&self, grid: impl Into<Grid>, bindings: &[KernelBinding]) -> Result<Option<Duration>, KernelError> {
+        self.run(grid.into(), bindings, |_| Ok(()))
+    }
+
+    pub fn dispatch_with_push_constants<Pc: BufferContents>(&self, grid: impl Into<Grid>, bindings: &[KernelBinding], push_constants: Pc
+    ) -> Result<Option<Duration>, KernelError> {
+        self.run(grid.into(), bindings, |command_buffer_builder| {
+            command_buffer_builder
+                .push_constants(self.pipeline.layout().clone(), 0, push_constants)
+                .map(|_| ())
+                .map_err(|error| KernelError::Recording(error.into()))
+        })
+    }
+
+    // buffers have to be host visible, like the ones storage_buffer_from_iter makes
+    pub fn read<T: BufferContents + Clone>(&self, buffer: &Subbuffer<[T]>) -> Result<Vec<T>, KernelError> {
+        Ok(buffer.read().map_err(KernelError::Readback)?.to_vec())
+    }
+
+    // copies the whole first layer of the image into host memory, tightly packed
+    pub fn read_image(&self, image: Arc<Image>) -> Result<Vec<u8>, KernelError> {
+        let extent = image.extent();
+        let size = image.format().block_size() * extent.iter().map(|&e| e as u64).product::<u64>();
+
+        let buffer = Buffer::new_slice::<u8>(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            size
+        ).map_err(KernelError::BufferCreation)?;
+
+        let mut command_buffer_builder = self.command_buffer_builder()?;
+        command_buffer_builder
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(image, buffer.clone()))
+            .map_err(|error| KernelError::Recording(error.into()))?;
+        self.submit_and_wait(command_buffer_builder)?;
+
+        self.read(&buffer)
+    }
+
+    fn run(&self,
+           grid: Grid,
+           bindings: &[KernelBinding],
+           record_push_constants: impl FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> Result<(), KernelError>
+    ) -> Result<Option<Duration>, KernelError> {
+        let mut command_buffer_builder = self.command_buffer_builder()?;
+        let mut timestamp_queries = self.timestamp_queries.as_ref()
+            .map(|timestamp_queries| timestamp_queries.lock().unwrap());
+
+        // timing is optional, a dispatch that cannot be timed still runs
+        let mut timestamp_scope = timestamp_queries.as_mut().and_then(|timestamp_queries| {
+            timestamp_queries.reset(&mut command_buffer_builder)
+                .and_then(|()| timestamp_queries.begin_scope(&mut command_buffer_builder, "dispatch"))
+                .inspect_err(|error| warn!("Not timing the dispatch: {error}"))
+                .ok()
+        });
+        self.record_dispatch(&mut command_buffer_builder, grid, bindings, record_push_constants)?;
+        if let (Some(timestamp_queries), Some(scope)) = (&timestamp_queries, timestamp_scope) {
+            timestamp_scope = timestamp_queries.end_scope(&mut command_buffer_builder, scope)
+                .inspect_err(|error| warn!("Not timing the dispatch: {error}"))
+                .ok()
+                .map(|()| scope);
+        }
+
+        self.submit_and_wait(command_buffer_builder)?;
+
+        let gpu_duration = timestamp_queries
+            .filter(|_| timestamp_scope.is_some())
+            .and_then(|timestamp_queries| timestamp_queries.results()
+                .inspect_err(|error| warn!("{error}"))
+                .ok())
+            .and_then(|results| results.first().map(|(_, duration)| *duration));
+        Ok(gpu_duration)
+    }
+
+    fn record_dispatch(&self,
+                       command_buffer_builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+                       grid: Grid,
+                       bindings: &[KernelBinding],
+                       record_push_constants: impl FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> Result<(), KernelError>
+    ) -> Result<(), KernelError> {
+        let pipeline_layout = self.pipeline.layout().clone();
+
+        command_buffer_builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .map_err(|error| KernelError::Recording(error.into()))?;
+
+        if !bindings.is_empty() {
+            let descriptor_set = DescriptorSet::new(
+                self.descriptor_set_allocator.clone(),
+                pipeline_layout.set_layouts()[0].clone(),
+                bindings.iter().map(KernelBinding::write),
+                []
+            ).map_err(KernelError::DescriptorSetCreation)?;
+
+            command_buffer_builder
+                .bind_descriptor_sets(PipelineBindPoint::Compute, pipeline_layout, 0, descriptor_set)
+                .map_err(|error| KernelError::Recording(error.into()))?;
+        }
+
+        record_push_constants(command_buffer_builder)?;
+
+        unsafe {
+            command_buffer_builder
+                .dispatch(grid.work_group_counts(self.local_size))
+                .map_err(|error| KernelError::Recording(error.into()))?;
+        }
+        Ok(())
+    }
+
+    fn command_buffer_builder(&self) -> Result<AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, KernelError> {
+        AutoCommandBufferBuilder::primary(
+            self.command_buffer_allocator.clone(),
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit
+        ).map_err(|error| KernelError::Recording(error.into()))
+    }
+
+    fn submit_and_wait(&self, command_buffer_builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) -> Result<(), KernelError> {
+        let command_buffer = command_buffer_builder.build()
+            .map_err(|error| KernelError::Recording(error.into()))?;
+
+        sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), command_buffer)
+            .map_err(|error| KernelError::Execution(error.into()))?
+            .then_signal_fence_and_flush()
+            .map_err(|error| KernelError::Execution(error.into()))?
+            .wait(None)
+            .map_err(|error| KernelError::Execution(error.into()))
+    }
+}
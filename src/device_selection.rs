@@ -0,0 +1,209 @@
+use std::env;
+use std::fmt::Write;
+use std::str::FromStr;
+use std::sync::Arc;
+use log::{info, warn};
+use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType};
+use vulkano::memory::MemoryHeapFlags;
+
+const DEVICE_ENV_VAR: &str = "VKPG_DEVICE";
+
+// VKPG_DEVICE accepts "discrete", "integrated", "index:N" (or just N), "name:SUBSTRING" (or any other text),
+// "vendor:ID" with ID in decimal or 0x-prefixed hex, and "uuid:UUID" with or without dashes
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum DeviceSelection {
+    #[default]
+    PreferDiscrete,
+    PreferIntegrated,
+    // position in the order the instance enumerates devices, as shown in the logged device table
+    Index(usize),
+    // case-insensitive substring of the device name
+    Name(String),
+    VendorId(u32),
+    Uuid([u8; 16]),
+}
+
+impl DeviceSelection {
+    pub fn from_env() -> Option<Self> {
+        let value = env::var(DEVICE_ENV_VAR).ok()?;
+        match value.parse() {
+            Ok(device_selection) => Some(device_selection),
+            Err(error) => {
+                warn!("Ignoring {}: {}", DEVICE_ENV_VAR, error);
+                None
+            }
+        }
+    }
+
+    pub(crate) fn matches(&self, index: usize, physical_device: &PhysicalDevice) -> bool {
+        let properties = physical_device.properties();
+        match self {
+            DeviceSelection::PreferDiscrete | DeviceSelection::PreferIntegrated => true,
+            DeviceSelection::Index(selected_index) => index == *selected_index,
+            DeviceSelection::Name(name) => properties.device_name.to_lowercase().contains(&name.to_lowercase()),
+            DeviceSelection::VendorId(vendor_id) => properties.vendor_id == *vendor_id,
+            DeviceSelection::Uuid(uuid) => properties.device_uuid.as_ref() == Some(uuid),
+        }
+    }
+
+    pub(crate) fn select(&self, physical_devices: Vec<Arc<PhysicalDevice>>) -> Option<Arc<PhysicalDevice>> {
+        let type_order = match self {
+            DeviceSelection::PreferIntegrated => [PhysicalDeviceType::IntegratedGpu, PhysicalDeviceType::DiscreteGpu],
+            _ => [PhysicalDeviceType::DiscreteGpu, PhysicalDeviceType::IntegratedGpu],
+        };
+
+        physical_devices.into_iter()
+            .min_by_key(|physical_device| {
+                type_order.iter()
+                    .position(|device_type| *device_type == physical_device.properties().device_type)
+                    .unwrap_or(type_order.len())
+            })
+    }
+}
+
+impl FromStr for DeviceSelection {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        let (kind, argument) = value.split_once(':').unwrap_or(("", value));
+
+        match kind.to_lowercase().as_str() {
+            "index" => argument.trim().parse()
+                .map(DeviceSelection::Index)
+                .map_err(|_| format!("invalid device index \"{argument}\"")),
+            "name" => Ok(DeviceSelection::Name(argument.to_string())),
+            "vendor" => parse_vendor_id(argument.trim())
+                .map(DeviceSelection::VendorId)
+                .ok_or_else(|| format!("invalid vendor ID \"{argument}\"")),
+            "uuid" => parse_uuid(argument.trim())
+                .map(DeviceSelection::Uuid)
+                .ok_or_else(|| format!("invalid device UUID \"{argument}\"")),
+            "" if value.eq_ignore_ascii_case("discrete") => Ok(DeviceSelection::PreferDiscrete),
+            "" if value.eq_ignore_ascii_case("integrated") => Ok(DeviceSelection::PreferIntegrated),
+            "" if value.is_empty() => Err("empty device selection".to_string()),
+            "" => Ok(value.parse().map(DeviceSelection::Index).unwrap_or_else(|_| DeviceSelection::Name(value.to_string()))),
+            _ => Ok(DeviceSelection::Name(value.to_string())),
+        }
+    }
+}
+
+fn parse_vendor_id(value: &str) -> Option<u32> {
+    match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+fn parse_uuid(value: &str) -> Option<[u8; 16]> {
+    let hex = value.replace('-', "");
+    if hex.len() != 32 || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut uuid = [0u8; 16];
+    for (i, byte) in uuid.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(uuid)
+}
+
+pub fn format_uuid(uuid: &[u8; 16]) -> String {
+    let mut formatted = String::with_capacity(36);
+    for (i, byte) in uuid.iter().enumerate() {
+        if matches!(i, 4 | 6 | 8 | 10) {
+            formatted.push('-');
+        }
+        write!(formatted, "{:02x}", byte).unwrap();
+    }
+    formatted
+}
+
+pub(crate) fn log_device_table(physical_devices: &[Arc<PhysicalDevice>]) {
+    let mut table = String::from("Physical devices:");
+    for (index, physical_device) in physical_devices.iter().enumerate() {
+        let properties = physical_device.properties();
+        let heaps = physical_device.memory_properties().memory_heaps.iter()
+            .map(|heap| {
+                let location = if heap.flags.intersects(MemoryHeapFlags::DEVICE_LOCAL) { "device" } else { "host" };
+                format!("{:.1} GiB {}", heap.size as f64 / (1u64 << 30) as f64, location)
+            })
+            .collect::<Vec<_>>();
+
+        write!(table, "\n  [{}] {:<40} | {:<13} | Vulkan {:<8} | vendor {:#06x} | uuid {} | heaps: {}",
+               index,
+               properties.device_name,
+               format!("{:?}", properties.device_type),
+               physical_device.api_version().to_string(),
+               properties.vendor_id,
+               properties.device_uuid.as_ref().map(format_uuid).unwrap_or_else(|| "--".to_string()),
+               heaps.join(", ")
+        ).unwrap();
+    }
+    info!("{}", table);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UUID: [u8; 16] = [0x12, 0x34, 0x56, 0x78, 0x9a, 0xbc, 0xde, 0xf0, 0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef];
+
+    #[test]
+    fn device_types() {
+        assert_eq!("discrete".parse(), Ok(DeviceSelection::PreferDiscrete));
+        assert_eq!(" Integrated ".parse(), Ok(DeviceSelection::PreferIntegrated));
+    }
+
+    #[test]
+    fn indices() {
+        assert_eq!("index:2".parse(), Ok(DeviceSelection::Index(2)));
+        assert_eq!("INDEX: 2".parse(), Ok(DeviceSelection::Index(2)));
+        assert_eq!("3".parse(), Ok(DeviceSelection::Index(3)));
+        assert!("index:first".parse::<DeviceSelection>().is_err());
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!("name:RTX".parse(), Ok(DeviceSelection::Name("RTX".to_string())));
+        // a number after name: is still a name
+        assert_eq!("name:780".parse(), Ok(DeviceSelection::Name("780".to_string())));
+        assert_eq!("GeForce".parse(), Ok(DeviceSelection::Name("GeForce".to_string())));
+        // unknown kinds are part of the name
+        assert_eq!("Intel(R): Arc".parse(), Ok(DeviceSelection::Name("Intel(R): Arc".to_string())));
+    }
+
+    #[test]
+    fn vendor_ids() {
+        assert_eq!("vendor:0x10de".parse(), Ok(DeviceSelection::VendorId(0x10de)));
+        assert_eq!("vendor:0X10DE".parse(), Ok(DeviceSelection::VendorId(0x10de)));
+        assert_eq!("vendor:4318".parse(), Ok(DeviceSelection::VendorId(0x10de)));
+        assert!("vendor:nvidia".parse::<DeviceSelection>().is_err());
+    }
+
+    #[test]
+    fn uuids() {
+        assert_eq!("uuid:12345678-9abc-def0-0123-456789abcdef".parse(), Ok(DeviceSelection::Uuid(UUID)));
+        assert_eq!("uuid:123456789ABCDEF00123456789ABCDEF".parse(), Ok(DeviceSelection::Uuid(UUID)));
+        assert!("uuid:12345678".parse::<DeviceSelection>().is_err());
+    }
+
+    #[test]
+    fn empty_selection_is_an_error() {
+        assert!("".parse::<DeviceSelection>().is_err());
+        assert!("  ".parse::<DeviceSelection>().is_err());
+    }
+
+    #[test]
+    fn uuid_round_trip() {
+        assert_eq!(parse_uuid(&format_uuid(&UUID)), Some(UUID));
+    }
+
+    #[test]
+    fn invalid_uuids() {
+        assert_eq!(parse_uuid("123456789abcdef00123456789abcdeg"), None);
+        assert_eq!(parse_uuid("123456789abcdef00123456789abcdef00"), None);
+        // 32 bytes, but not 32 hex digits
+        assert_eq!(parse_uuid(&"é".repeat(16)), None);
+    }
+}
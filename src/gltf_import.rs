@@ -0,0 +1,198 @@
+use std::path::Path;
+use glam::Mat4;
+use gltf::camera::Projection as GltfProjection;
+use gltf::image::Format;
+use gltf::mesh::Mode;
+use log::warn;
+use crate::mesh_processing::{generate_normals, NormalMode};
+use crate::scene::{Material, Primitive, Projection, Scene, SceneCamera, SceneMesh, SceneNode, SceneVertex, Texture};
+
+// imports the default scene, or the first one when the file does not name a default;
+// buffers and images may be embedded, in a .glb or next to the .gltf file
+pub(crate) fn load_gltf(path: &Path) -> Result<Scene, String> {
+    let (document, buffers, images) = gltf::import(path)
+        .map_err(|error| format!("Failed to import {}: {error}", path.display()))?;
+
+    let gltf_scene = document.default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| format!("{} contains no scene", path.display()))?;
+
+    let nodes = document.nodes()
+        .map(|node| SceneNode {
+            name: node.name().map(str::to_string),
+            transform: Mat4::from_cols_array_2d(&node.transform().matrix()),
+            children: node.children().map(|child| child.index()).collect(),
+            mesh: node.mesh().map(|mesh| mesh.index()),
+            camera: node.camera().map(|camera| camera.index()),
+        })
+        .collect();
+
+    let meshes = document.meshes()
+        .map(|mesh| {
+            let primitives = mesh.primitives()
+                .filter_map(|primitive| {
+                    let name = mesh.name().unwrap_or("unnamed");
+                    import_primitive(&primitive, &buffers)
+                        .inspect_err(|error| warn!("Skipping primitive {} of mesh {}: {}", primitive.index(), name, error))
+                        .ok()
+                })
+                .collect();
+
+            SceneMesh {
+                name: mesh.name().map(str::to_string),
+                primitives,
+            }
+        })
+        .collect();
+
+    let materials = document.materials()
+        .map(|material| {
+            let pbr = material.pbr_metallic_roughness();
+            Material {
+                name: material.name().map(str::to_string),
+                base_color_factor: pbr.base_color_factor(),
+                base_color_texture: pbr.base_color_texture().map(|info| info.texture().source().index()),
+                metallic_factor: pbr.metallic_factor(),
+                roughness_factor: pbr.roughness_factor(),
+                metallic_roughness_texture: pbr.metallic_roughness_texture().map(|info| info.texture().source().index()),
+                emissive_factor: material.emissive_factor(),
+                double_sided: material.double_sided(),
+            }
+        })
+        .collect();
+
+    // indexed like the images of the document, so materials can refer to them directly
+    let textures = images.into_iter()
+        .enumerate()
+        .map(|(index, image)| {
+            to_rgba8(&image).unwrap_or_else(|| {
+                warn!("Image {} has unsupported format {:?}, using white instead", index, image.format);
+                Texture { width: 1, height: 1, pixels: vec![255; 4] }
+            })
+        })
+        .collect();
+
+    let cameras = document.cameras()
+        .map(|camera| SceneCamera {
+            name: camera.name().map(str::to_string),
+            projection: match camera.projection() {
+                GltfProjection::Perspective(perspective) => Projection::Perspective {
+                    yfov: perspective.yfov(),
+                    aspect_ratio: perspective.aspect_ratio(),
+                    znear: perspective.znear(),
+                    zfar: perspective.zfar(),
+                },
+                GltfProjection::Orthographic(orthographic) => Projection::Orthographic {
+                    xmag: orthographic.xmag(),
+                    ymag: orthographic.ymag(),
+                    znear: orthographic.znear(),
+                    zfar: orthographic.zfar(),
+                },
+            },
+        })
+        .collect();
+
+    let scene = Scene {
+        nodes,
+        root_nodes: gltf_scene.nodes().map(|node| node.index()).collect(),
+        meshes,
+        materials,
+        textures,
+        cameras,
+    };
+    scene.check_node_hierarchy()
+        .map_err(|error| format!("Invalid node hierarchy in {}: {error}", path.display()))?;
+    Ok(scene)
+}
+
+fn import_primitive(primitive: &gltf::Primitive, buffers: &[gltf::buffer::Data]) -> Result<Primitive, String> {
+    if primitive.mode() != Mode::Triangles {
+        return Err(format!("mode {:?} is not supported", primitive.mode()));
+    }
+
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data.0[..]));
+    let positions = reader.read_positions()
+        .ok_or("no positions")?
+        .collect::<Vec<_>>();
+    let indices = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect::<Vec<_>>(),
+        None => (0..positions.len() as u32).collect(),
+    };
+    if indices.iter().any(|&index| index as usize >= positions.len()) {
+        return Err("index out of range".to_string());
+    }
+    // vertices have a single set of texture coordinates, the one the base color texture is sampled with
+    let pbr = primitive.material().pbr_metallic_roughness();
+    let tex_coord_set = pbr.base_color_texture().map_or(0, |info| info.tex_coord());
+    if let Some(info) = pbr.metallic_roughness_texture()
+        && info.tex_coord() != tex_coord_set {
+        warn!("The metallic-roughness texture uses TEXCOORD_{}, sampling it with TEXCOORD_{} instead", info.tex_coord(), tex_coord_set);
+    }
+    let tex_coords = reader.read_tex_coords(tex_coord_set)
+        .map(|tex_coords| tex_coords.into_f32().collect::<Vec<_>>())
+        .filter(|tex_coords| tex_coords.len() == positions.len())
+        .unwrap_or_else(|| vec![[0.0; 2]; positions.len()]);
+
+    let colors = reader.read_colors(0)
+        .map(|colors| colors.into_rgba_f32().collect::<Vec<_>>())
+        .filter(|colors| colors.len() == positions.len())
+        .unwrap_or_else(|| vec![[1.0; 4]; positions.len()]);
+    let normals = reader.read_normals()
+        .map(|normals| normals.collect::<Vec<_>>())
+        .filter(|normals| normals.len() == positions.len());
+
+    let mut primitive = Primitive {
+        vertices: (0..positions.len())
+            .map(|i| SceneVertex {
+                position: positions[i],
+                normal: normals.as_ref().map_or([0.0; 3], |normals| normals[i]),
+                tex_coord: tex_coords[i],
+                color: colors[i],
+            })
+            .collect(),
+        indices,
+        material: primitive.material().index(),
+    };
+    // glTF requires flat shading when normals are missing
+    if normals.is_none() {
+        generate_normals(&mut primitive, NormalMode::Flat);
+    }
+    Ok(primitive)
+}
+
+// None for floating point formats; 16-bit channels keep their most significant byte
+fn to_rgba8(image: &gltf::image::Data) -> Option<Texture> {
+    let channels = |pixel: &[u8], bytes_per_channel: usize| -> [u8; 4] {
+        let mut rgba = [0, 0, 0, 255];
+        let channel_count = pixel.len() / bytes_per_channel;
+        for channel in 0..channel_count {
+            // little endian, so the most significant byte is the last one
+            rgba[channel] = pixel[channel * bytes_per_channel + bytes_per_channel - 1];
+        }
+        if channel_count == 1 {
+            rgba[1] = rgba[0];
+            rgba[2] = rgba[0];
+        }
+        rgba
+    };
+
+    let (channel_count, bytes_per_channel) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        _ => return None,
+    };
+
+    Some(Texture {
+        width: image.width,
+        height: image.height,
+        pixels: image.pixels.chunks_exact(channel_count * bytes_per_channel)
+            .flat_map(|pixel| channels(pixel, bytes_per_channel))
+            .collect(),
+    })
+}
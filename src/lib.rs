@@ -1,145 +1,417 @@
-
-
+use std::collections::HashMap;
+use std::env;
+use std::panic::RefUnwindSafe;
+use std::path::PathBuf;
 use std::sync::Arc;
-use log::{debug, error, info, warn};
+use log::{info, warn};
 use vulkano::command_buffer::allocator::{StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo};
 use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
 use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, DeviceFeatures, Queue, QueueCreateInfo, QueueFlags};
-use vulkano::device::physical::{PhysicalDeviceType};
 use vulkano::instance::debug::{DebugUtilsMessageSeverity, DebugUtilsMessageType, DebugUtilsMessenger, DebugUtilsMessengerCallback, DebugUtilsMessengerCreateInfo};
 use vulkano::instance::{Instance, InstanceCreateInfo, InstanceExtensions};
 use vulkano::memory::allocator::{StandardMemoryAllocator};
-use vulkano::VulkanLibrary;
+use vulkano::pipeline::cache::PipelineCache;
+use vulkano::{Validated, Version, VulkanError, VulkanLibrary};
 use winit::event_loop::EventLoop;
 
-const DEFAULT_INSTANCE_EXTENSIONS: InstanceExtensions = InstanceExtensions {
+pub mod assets;
+mod atomic_write;
+pub mod benchmark;
+mod compute_kernel;
+mod device_selection;
+mod gltf_import;
+pub mod logging;
+pub mod mesh;
+pub mod mesh_cache;
+pub mod mesh_processing;
+mod pipeline_cache;
+mod ply_import;
+mod queue_selection;
+pub mod scene;
+mod setup_error;
+mod shader_compiler;
+mod stl_import;
+mod timestamps;
+mod validation;
+
+pub use compute_kernel::{ComputeKernel, Grid, KernelBinding, KernelError};
+pub use device_selection::{format_uuid, DeviceSelection};
+pub use setup_error::{DeviceCandidate, QueueFamilyCandidate, SetupError};
+pub use shader_compiler::{load_shader_module, ShaderCompiler, ShaderDefines};
+pub use timestamps::{TimestampError, TimestampQueries, TimestampScope};
+pub use validation::{ReportedMessage, ValidationFailure, ValidationReport};
+use device_selection::log_device_table;
+use logging::{log_debug_message, DebugMessage, DebugMessageSink};
+use queue_selection::QueueFamilySelection;
+
+const VALIDATION_INSTANCE_EXTENSIONS: InstanceExtensions = InstanceExtensions {
     ext_debug_utils: true,
     ..InstanceExtensions::empty()
 };
 const LAYERS: [&str; 1] = ["VK_LAYER_KHRONOS_validation"];
+const VALIDATION_ENV_VAR: &str = "VKPG_VALIDATION";
+const ALL_DEBUG_MESSAGE_SEVERITIES: DebugUtilsMessageSeverity = DebugUtilsMessageSeverity::ERROR
+    .union(DebugUtilsMessageSeverity::WARNING)
+    .union(DebugUtilsMessageSeverity::INFO)
+    .union(DebugUtilsMessageSeverity::VERBOSE);
 
 pub struct CommonItems {
     pub library: Arc<VulkanLibrary>,
     pub instance: Arc<Instance>,
-    pub debug_callback: DebugUtilsMessenger,
+    pub debug_callback: Option<DebugUtilsMessenger>,
+    // stays empty when validation is disabled
+    pub validation_report: Arc<ValidationReport>,
     pub device: Arc<Device>,
+    // matches the flags requested from the builder, and presents when presentation support was requested
     pub queue: Arc<Queue>,
+    pub graphics_queue: Option<Arc<Queue>>,
+    // prefers a family without graphics support, so work can overlap with rendering
+    pub compute_queue: Option<Arc<Queue>>,
+    // prefers a transfer-only family
+    pub transfer_queue: Option<Arc<Queue>>,
+    pub present_queue: Option<Arc<Queue>>,
     pub memory_allocator: Arc<StandardMemoryAllocator>,
     pub descriptor_set_allocator: Arc<StandardDescriptorSetAllocator>,
     pub command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+    // loaded from the user cache dir, see save_pipeline_cache; None when the driver could not create one
+    pub pipeline_cache: Option<Arc<PipelineCache>>,
+    // None when there is no user cache dir, the cache then only lives for this run
+    pub pipeline_cache_path: Option<PathBuf>,
+}
+
+impl CommonItems {
+    // has to be called before exiting for the next run to benefit from the pipelines created in this one
+    pub fn save_pipeline_cache(&self) {
+        let (Some(pipeline_cache), Some(path)) = (&self.pipeline_cache, &self.pipeline_cache_path) else {
+            return;
+        };
+        match pipeline_cache::save_pipeline_cache(pipeline_cache, &self.device, path) {
+            Ok(()) => info!("Saved pipeline cache to {}", path.display()),
+            Err(error) => warn!("Failed to save pipeline cache to {}: {}", path.display(), error),
+        }
+    }
 }
 
 pub fn get_debug_callback(instance: Arc<Instance>) -> DebugUtilsMessenger {
-    pretty_env_logger::init();
-    
+    try_get_debug_callback(instance, ALL_DEBUG_MESSAGE_SEVERITIES, Vec::new()).expect("Failed to create debug callback")
+}
+
+// messages are always forwarded to log, and additionally to every sink
+pub fn try_get_debug_callback(instance: Arc<Instance>,
+                              message_severity: DebugUtilsMessageSeverity,
+                              sinks: Vec<DebugMessageSink>
+) -> Result<DebugUtilsMessenger, Validated<VulkanError>> {
     unsafe {
         DebugUtilsMessenger::new(
             instance.clone(),
             DebugUtilsMessengerCreateInfo {
-                message_severity: DebugUtilsMessageSeverity::ERROR
-                    | DebugUtilsMessageSeverity::WARNING
-                    | DebugUtilsMessageSeverity::INFO
-                    | DebugUtilsMessageSeverity::VERBOSE,
+                message_severity,
                 message_type: DebugUtilsMessageType::GENERAL
                     | DebugUtilsMessageType::PERFORMANCE
                     | DebugUtilsMessageType::VALIDATION,
                 ..DebugUtilsMessengerCreateInfo::user_callback(DebugUtilsMessengerCallback::new(
-                    |message_severity,
-                     message_type,
-                     callback_data| {
-                        if message_severity.intersects(DebugUtilsMessageSeverity::ERROR) {
-                            error!("({:?}) {}", message_type, callback_data.message);
-                        } else if message_severity.intersects(DebugUtilsMessageSeverity::WARNING) {
-                            warn!("({:?}) {}", message_type, callback_data.message);
-                        } else if message_severity.intersects(DebugUtilsMessageSeverity::INFO) {
-                            info!("({:?}) {}", message_type, callback_data.message);
-                        } else {
-                            debug!("({:?}) {}", message_type, callback_data.message);
+                    move |message_severity,
+                          message_type,
+                          callback_data| {
+                        let message = DebugMessage {
+                            severity: message_severity,
+                            message_type,
+                            id_name: callback_data.message_id_name,
+                            id_number: callback_data.message_id_number,
+                            message: callback_data.message,
+                        };
+                        log_debug_message(&message);
+                        for sink in &sinks {
+                            sink(&message);
                         }
                     }
                 ))
 
             }
-        ).expect("Failed to create debug callback")
+        )
     }
 }
 
+pub struct CommonItemsBuilder<'a> {
+    instance_extensions: InstanceExtensions,
+    device_extensions: DeviceExtensions,
+    device_features: DeviceFeatures,
+    validation_layers: bool,
+    debug_message_severity: DebugUtilsMessageSeverity,
+    debug_message_sinks: Vec<DebugMessageSink>,
+    device_selection: Option<DeviceSelection>,
+    api_version: Option<Version>,
+    queue_flags: QueueFlags,
+    event_loop: Option<&'a EventLoop<()>>,
+}
+
+impl Default for CommonItemsBuilder<'_> {
+    fn default() -> Self {
+        CommonItemsBuilder {
+            instance_extensions: InstanceExtensions::empty(),
+            device_extensions: DeviceExtensions::empty(),
+            device_features: DeviceFeatures::empty(),
+            validation_layers: validation_requested_by_env(),
+            debug_message_severity: ALL_DEBUG_MESSAGE_SEVERITIES,
+            debug_message_sinks: Vec::new(),
+            device_selection: None,
+            api_version: None,
+            queue_flags: QueueFlags::empty(),
+            event_loop: None,
+        }
+    }
+}
+
+impl<'a> CommonItemsBuilder<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_instance_extensions(mut self, instance_extensions: InstanceExtensions) -> Self {
+        self.instance_extensions = self.instance_extensions.union(&instance_extensions);
+        self
+    }
+
+    pub fn with_device_extensions(mut self, device_extensions: DeviceExtensions) -> Self {
+        self.device_extensions = self.device_extensions.union(&device_extensions);
+        self
+    }
+
+    pub fn with_device_features(mut self, device_features: DeviceFeatures) -> Self {
+        self.device_features = self.device_features.union(&device_features);
+        self
+    }
+
+    // overrides the VKPG_VALIDATION environment variable
+    pub fn with_validation_layers(mut self, validation_layers: bool) -> Self {
+        self.validation_layers = validation_layers;
+        self
+    }
+
+    pub fn with_debug_message_severity(mut self, debug_message_severity: DebugUtilsMessageSeverity) -> Self {
+        self.debug_message_severity = debug_message_severity;
+        self
+    }
+
+    pub fn with_debug_message_sink(mut self, sink: impl Fn(&DebugMessage) + RefUnwindSafe + Send + Sync + 'static) -> Self {
+        self.debug_message_sinks.push(Arc::new(sink));
+        self
+    }
+
+    // overrides the VKPG_DEVICE environment variable
+    pub fn with_device_selection(mut self, device_selection: DeviceSelection) -> Self {
+        self.device_selection = Some(device_selection);
+        self
+    }
+
+    // highest version the instance may use, devices supporting less are skipped
+    pub fn with_api_version(mut self, api_version: Version) -> Self {
+        self.api_version = Some(api_version);
+        self
+    }
+
+    pub fn with_queue_flags(mut self, queue_flags: QueueFlags) -> Self {
+        self.queue_flags = queue_flags;
+        self
+    }
+
+    pub fn with_presentation_support(mut self, event_loop: &'a EventLoop<()>) -> Self {
+        self.event_loop = Some(event_loop);
+        self
+    }
+
+    pub fn build(self) -> Result<CommonItems, SetupError> {
+        let library = VulkanLibrary::new().map_err(SetupError::LibraryNotFound)?;
+
+        let mut validation = self.validation_layers;
+        if validation {
+            let library_layers = library.layer_properties().map_err(SetupError::LayerEnumeration)?
+                .map(|l| {l.name().to_string()})
+                .collect::<Vec<_>>();
+            if let Some(layer) = LAYERS.iter().find(|layer| !library_layers.iter().any(|l| {l == *layer})) {
+                warn!("Layer {} not available in library, continuing without validation", layer);
+                validation = false;
+            } else if !library.supported_extensions().contains(&VALIDATION_INSTANCE_EXTENSIONS) {
+                warn!("Extension VK_EXT_debug_utils not available in library, continuing without validation");
+                validation = false;
+            }
+        }
+
+        let (layers, validation_extensions): (&[&str], _) = if validation {
+            (&LAYERS, VALIDATION_INSTANCE_EXTENSIONS)
+        } else {
+            (&[], InstanceExtensions::empty())
+        };
+
+        let instance = Instance::new(
+            library.clone(),
+            InstanceCreateInfo {
+                enabled_layers: layers.iter().map(|l| {l.to_string()}).collect::<Vec<_>>(),
+                enabled_extensions: validation_extensions.union(&self.instance_extensions),
+                max_api_version: self.api_version,
+                ..Default::default()
+            }
+        ).map_err(SetupError::InstanceCreation)?;
+
+        let validation_report = Arc::new(ValidationReport::new(validation));
+        let debug_callback = if validation {
+            let mut sinks = self.debug_message_sinks.clone();
+            let report_sink = validation_report.clone();
+            sinks.push(Arc::new(move |message: &DebugMessage| report_sink.record(message)));
+
+            Some(try_get_debug_callback(instance.clone(), self.debug_message_severity, sinks)
+                .map_err(SetupError::DebugCallbackCreation)?)
+        } else {
+            None
+        };
+
+        let device_selection = self.device_selection.clone()
+            .or_else(DeviceSelection::from_env)
+            .unwrap_or_default();
+
+        let physical_devices = instance
+            .enumerate_physical_devices().map_err(SetupError::DeviceEnumeration)?
+            .collect::<Vec<_>>();
+        log_device_table(&physical_devices);
+
+        let mut attempted_devices = Vec::new();
+        let physical_devices = physical_devices.into_iter().enumerate()
+            .filter(|(index, physical_device)| {
+                let missing_extensions = self.device_extensions.difference(physical_device.supported_extensions());
+                let rejection = if !device_selection.matches(*index, physical_device) {
+                    format!("not selected by {:?}", device_selection)
+                } else if !missing_extensions.is_empty() {
+                    format!("missing extensions {:?}", missing_extensions)
+                } else if self.api_version.is_some_and(|api_version| physical_device.api_version() < api_version) {
+                    format!("API version {} is lower than {}", physical_device.api_version(), self.api_version.unwrap())
+                } else {
+                    return true;
+                };
+                attempted_devices.push(DeviceCandidate {
+                    name: physical_device.properties().device_name.clone(),
+                    device_type: physical_device.properties().device_type,
+                    rejection,
+                });
+                false
+            })
+            .map(|(_, physical_device)| physical_device)
+            .collect::<Vec<_>>();
+
+        let physical_device = device_selection.select(physical_devices)
+            .ok_or_else(|| SetupError::NoSuitableDevice { attempted: attempted_devices })?;
+        let device_name = physical_device.properties().device_name.clone();
+        info!("Using physical device {}", device_name);
+
+        let queue_families = physical_device
+            .queue_family_properties().iter().enumerate()
+            .map(|(index, queue_family_properties)| QueueFamilyCandidate {
+                index: index as u32,
+                queue_flags: queue_family_properties.queue_flags,
+                presentation_support: self.event_loop.map(|event_loop| {
+                    physical_device.presentation_support(index as u32, event_loop).unwrap_or(false)
+                }),
+            })
+            .collect::<Vec<_>>();
+
+        let queue_family_selection = QueueFamilySelection::new(&queue_families, self.queue_flags)
+            .ok_or_else(|| SetupError::NoSuitableQueueFamily {
+                device: device_name.clone(),
+                attempted: queue_families.clone(),
+            })?;
+        info!("Using queue families primary: {}, graphics: {:?}, compute: {:?}, transfer: {:?}, present: {:?}",
+              queue_family_selection.primary,
+              queue_family_selection.graphics,
+              queue_family_selection.compute,
+              queue_family_selection.transfer,
+              queue_family_selection.present);
+
+        let queue_family_indices = queue_family_selection.unique_indices();
+
+        let (device, queues) = Device::new(
+            physical_device.clone(),
+            DeviceCreateInfo {
+                queue_create_infos: queue_family_indices.iter().map(|&queue_family_index| QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }).collect(),
+                enabled_extensions: self.device_extensions,
+                enabled_features: self.device_features,
+                ..Default::default()
+            }
+        ).map_err(|error| SetupError::DeviceCreation { device: device_name, error })?;
+
+        let queues = queue_family_indices.into_iter().zip(queues).collect::<HashMap<_, _>>();
+        let queue_for = |queue_family_index: Option<u32>| {
+            queue_family_index.map(|queue_family_index| queues[&queue_family_index].clone())
+        };
+
+        let queue = queues[&queue_family_selection.primary].clone();
+        let graphics_queue = queue_for(queue_family_selection.graphics);
+        let compute_queue = queue_for(queue_family_selection.compute);
+        let transfer_queue = queue_for(queue_family_selection.transfer);
+        let present_queue = queue_for(queue_family_selection.present);
+
+        let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(
+            device.clone())
+        );
+        let descriptor_set_allocator = Arc::new(StandardDescriptorSetAllocator::new(
+            device.clone(), Default::default())
+        );
+        let command_buffer_allocator = Arc::new(StandardCommandBufferAllocator::new(
+            device.clone(), StandardCommandBufferAllocatorCreateInfo::default()
+        ));
+
+        let pipeline_cache_path = pipeline_cache::cache_file_path(&device);
+        let pipeline_cache = pipeline_cache::load_pipeline_cache(device.clone(), pipeline_cache_path.as_deref());
+
+        Ok(CommonItems{
+            library,
+            instance,
+            debug_callback,
+            validation_report,
+            device,
+            queue,
+            graphics_queue,
+            compute_queue,
+            transfer_queue,
+            present_queue,
+            memory_allocator,
+            descriptor_set_allocator,
+            command_buffer_allocator,
+            pipeline_cache,
+            pipeline_cache_path,
+        })
+    }
+}
+
+fn validation_requested_by_env() -> bool {
+    env::var(VALIDATION_ENV_VAR)
+        .is_ok_and(|value| matches!(value.to_lowercase().as_str(), "1" | "true" | "on"))
+}
+
 pub fn get_common_vulkan_items(instance_extensions: Option<InstanceExtensions>,
                                device_extensions: Option<DeviceExtensions>,
                                device_features: Option<DeviceFeatures>,
                                queue_flag: QueueFlags,
                                event_loop: Option<&EventLoop<()>>
 ) -> CommonItems {
-    let library = VulkanLibrary::new().expect("No local Vulkan library/dll");
-
-    let mut library_layers = library.layer_properties().unwrap();
-    LAYERS.iter().for_each(|layer| {
-        library_layers.find(|l| {l.name() == *layer})
-            .expect(format!("Layer {} not available in library", *layer).as_str());
-    });
-
-    let instance = Instance::new(
-        library.clone(),
-        InstanceCreateInfo {
-            enabled_layers: LAYERS.iter().map(|l| {l.to_string()}).collect::<Vec<_>>(),
-            enabled_extensions: DEFAULT_INSTANCE_EXTENSIONS.union(&instance_extensions.unwrap_or_default()),
-            ..Default::default()
-        }
-    ).expect("Failed to create instance");
-
-    let debug_callback = get_debug_callback(instance.clone());
-
-    let physical_device = instance
-        .enumerate_physical_devices().unwrap()
-        .filter(|physical_device|
-            physical_device.supported_extensions().contains(&device_extensions.unwrap_or_default()))
-        .min_by_key(|physical_device| match physical_device.properties().device_type {
-            PhysicalDeviceType::DiscreteGpu => 0,
-            PhysicalDeviceType::IntegratedGpu => 1,
-            _ => 2,
-        }).unwrap();
-
-    let queue_family_index = physical_device
-        .queue_family_properties().iter().enumerate()
-        .position(|(index, queue_family_properties)| {
-            queue_family_properties.queue_flags.contains(queue_flag)
-                && event_loop.is_some_and(|event_loop| physical_device.presentation_support(index as u32, event_loop).unwrap())
-        })
-        .expect("No queue with appropriate support available") as u32;
+    try_get_common_vulkan_items(instance_extensions, device_extensions, device_features, queue_flag, event_loop)
+        .unwrap_or_else(|error| panic!("{error}"))
+}
 
-    let (device, mut queues) = Device::new(
-        physical_device.clone(),
-        DeviceCreateInfo {
-            queue_create_infos: vec![QueueCreateInfo {
-                queue_family_index,
-                ..Default::default()
-            }],
-            enabled_extensions: device_extensions.unwrap_or_default(),
-            enabled_features: device_features.unwrap_or_default(),
-            ..Default::default()
-        }
-    ).expect("Failed to create device");
-
-    let queue = queues.next().unwrap();
-
-    let memory_allocator = Arc::new(StandardMemoryAllocator::new_default(
-        device.clone())
-    );
-    let descriptor_set_allocator = Arc::new(StandardDescriptorSetAllocator::new(
-        device.clone(), Default::default())
-    );
-    let command_buffer_allocator = Arc::new(StandardCommandBufferAllocator::new(
-        device.clone(), StandardCommandBufferAllocatorCreateInfo::default()
-    ));
-
-    CommonItems{
-        library,
-        instance,
-        debug_callback,
-        device,
-        queue,
-        memory_allocator,
-        descriptor_set_allocator,
-        command_buffer_allocator,
+pub fn try_get_common_vulkan_items(instance_extensions: Option<InstanceExtensions>,
+                                   device_extensions: Option<DeviceExtensions>,
+                                   device_features: Option<DeviceFeatures>,
+                                   queue_flag: QueueFlags,
+                                   event_loop: Option<&EventLoop<()>>
+) -> Result<CommonItems, SetupError> {
+    let mut builder = CommonItemsBuilder::new()
+        .with_instance_extensions(instance_extensions.unwrap_or_default())
+        .with_device_extensions(device_extensions.unwrap_or_default())
+        .with_device_features(device_features.unwrap_or_default())
+        .with_queue_flags(queue_flag);
+    if let Some(event_loop) = event_loop {
+        builder = builder.with_presentation_support(event_loop);
     }
+    builder.build()
 }
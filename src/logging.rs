@@ -0,0 +1,31 @@
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+use log::{debug, error, info, warn};
+use vulkano::instance::debug::{DebugUtilsMessageSeverity, DebugUtilsMessageType};
+
+pub type DebugMessageSink = Arc<dyn Fn(&DebugMessage) + RefUnwindSafe + Send + Sync>;
+
+pub struct DebugMessage<'a> {
+    pub severity: DebugUtilsMessageSeverity,
+    pub message_type: DebugUtilsMessageType,
+    pub id_name: Option<&'a str>,
+    pub id_number: i32,
+    pub message: &'a str,
+}
+
+// safe to call any number of times, and leaves an already installed logger in place
+pub fn init_logger() {
+    pretty_env_logger::try_init().ok();
+}
+
+pub fn log_debug_message(message: &DebugMessage) {
+    if message.severity.intersects(DebugUtilsMessageSeverity::ERROR) {
+        error!("({:?}) {}", message.message_type, message.message);
+    } else if message.severity.intersects(DebugUtilsMessageSeverity::WARNING) {
+        warn!("({:?}) {}", message.message_type, message.message);
+    } else if message.severity.intersects(DebugUtilsMessageSeverity::INFO) {
+        info!("({:?}) {}", message.message_type, message.message);
+    } else {
+        debug!("({:?}) {}", message.message_type, message.message);
+    }
+}
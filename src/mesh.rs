@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::str::FromStr;
+use glam::Vec3;
+use obj::raw::object::Polygon;
+use crate::mesh_processing::{generate_normals, NormalMode};
+use crate::scene::{Primitive, SceneVertex};
+
+// where vertex normals come from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalSource {
+    // one normal per triangle, so every triangle gets its own three vertices
+    Face,
+    // area-weighted average of the normals of the triangles around a position
+    Vertex,
+    // the vn entries of the file, every face has to reference them
+    File,
+}
+
+// the axis that points up in the file, meshes are converted to Y-up
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UpAxis {
+    #[default]
+    Y,
+    Z,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeshOptions {
+    // None uses the file normals when every face has them and face normals otherwise
+    pub normals: Option<NormalSource>,
+    pub up: UpAxis,
+    pub scale: f32,
+}
+
+#[derive(Clone, Copy)]
+struct Corner {
+    position: usize,
+    normal: Option<usize>,
+}
+
+impl Default for MeshOptions {
+    fn default() -> Self {
+        MeshOptions {
+            normals: None,
+            up: UpAxis::Y,
+            scale: 1.0,
+        }
+    }
+}
+
+impl NormalSource {
+    pub const ALL: [NormalSource; 3] = [NormalSource::Face, NormalSource::Vertex, NormalSource::File];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NormalSource::Face => "face",
+            NormalSource::Vertex => "vertex",
+            NormalSource::File => "file",
+        }
+    }
+}
+
+impl Display for NormalSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for NormalSource {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        NormalSource::ALL.into_iter()
+            .find(|normal_source| normal_source.name().eq_ignore_ascii_case(value))
+            .ok_or_else(|| format!("unknown normal source \"{value}\", expected face, vertex or file"))
+    }
+}
+
+impl FromStr for UpAxis {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "y" => Ok(UpAxis::Y),
+            "z" => Ok(UpAxis::Z),
+            _ => Err(format!("unknown up axis \"{value}\", expected y or z")),
+        }
+    }
+}
+
+impl UpAxis {
+    // rotates around X, so handedness and winding are kept
+    fn to_y_up(self, vector: Vec3) -> Vec3 {
+        match self {
+            UpAxis::Y => vector,
+            UpAxis::Z => Vec3::new(vector.x, vector.z, -vector.y),
+        }
+    }
+}
+
+// errors are returned as a message, ready to be shown to the user
+pub fn load_mesh(path: &Path, options: &MeshOptions) -> Result<Primitive, String> {
+    let file = File::open(path).map_err(|error| format!("Failed to open {}: {error}", path.display()))?;
+    let raw_obj = obj::raw::parse_obj(BufReader::new(file))
+        .map_err(|error| format!("Failed to parse {}: {error}", path.display()))?;
+
+    let positions = raw_obj.positions.iter()
+        .map(|&(x, y, z, _)| options.up.to_y_up(Vec3::new(x, y, z)) * options.scale)
+        .collect::<Vec<_>>();
+    let file_normals = raw_obj.normals.iter()
+        .map(|&(x, y, z)| options.up.to_y_up(Vec3::new(x, y, z)).normalize_or_zero())
+        .collect::<Vec<_>>();
+
+    let mut triangles = Vec::new();
+    for polygon in &raw_obj.polygons {
+        let corners = match polygon {
+            Polygon::P(positions) => positions.iter()
+                .map(|&position| Corner { position, normal: None })
+                .collect::<Vec<_>>(),
+            Polygon::PT(corners) => corners.iter()
+                .map(|&(position, _)| Corner { position, normal: None })
+                .collect(),
+            Polygon::PN(corners) => corners.iter()
+                .map(|&(position, normal)| Corner { position, normal: Some(normal) })
+                .collect(),
+            Polygon::PTN(corners) => corners.iter()
+                .map(|&(position, _, normal)| Corner { position, normal: Some(normal) })
+                .collect(),
+        };
+        if corners.iter().any(|corner| corner.position >= positions.len()
+            || corner.normal.is_some_and(|normal| normal >= file_normals.len())) {
+            return Err(format!("{} references a vertex or normal that does not exist", path.display()));
+        }
+
+        // fan triangulation, fine for the convex polygons exporters write
+        for i in 1..corners.len().saturating_sub(1) {
+            triangles.push([corners[0], corners[i], corners[i + 1]]);
+        }
+    }
+
+    if triangles.is_empty() {
+        return Err(format!("{} contains no faces", path.display()));
+    }
+
+    let has_file_normals = triangles.iter().flatten().all(|corner| corner.normal.is_some());
+    let normal_source = options.normals
+        .unwrap_or(if has_file_normals { NormalSource::File } else { NormalSource::Face });
+
+    match normal_source {
+        NormalSource::Face => Ok(with_generated_normals(&positions, &triangles, NormalMode::Flat)),
+        NormalSource::Vertex => Ok(with_generated_normals(&positions, &triangles, NormalMode::AreaWeighted)),
+        NormalSource::File if has_file_normals => Ok(with_file_normals(&positions, &file_normals, &triangles)),
+        NormalSource::File => Err(format!("{} does not have a normal for every face corner", path.display())),
+    }
+}
+
+fn vertex(position: Vec3, normal: Vec3) -> SceneVertex {
+    SceneVertex {
+        position: position.to_array(),
+        normal: normal.to_array(),
+        ..Default::default()
+    }
+}
+
+fn with_generated_normals(positions: &[Vec3], triangles: &[[Corner; 3]], mode: NormalMode) -> Primitive {
+    let mut primitive = Primitive {
+        vertices: positions.iter().map(|&position| vertex(position, Vec3::ZERO)).collect(),
+        indices: triangles.iter().flatten().map(|corner| corner.position as u32).collect(),
+        material: None,
+    };
+    generate_normals(&mut primitive, mode);
+    primitive
+}
+
+// one vertex per unique (position, normal) pair
+fn with_file_normals(positions: &[Vec3], normals: &[Vec3], triangles: &[[Corner; 3]]) -> Primitive {
+    let mut primitive = Primitive::default();
+    let mut vertex_indices = HashMap::new();
+
+    for corner in triangles.iter().flatten() {
+        let normal = corner.normal.unwrap();
+        let index = *vertex_indices.entry((corner.position, normal)).or_insert_with(|| {
+            primitive.vertices.push(vertex(positions[corner.position], normals[normal]));
+            primitive.vertices.len() as u32 - 1
+        });
+        primitive.indices.push(index);
+    }
+
+    primitive
+}
@@ -0,0 +1,402 @@
+use std::ffi::OsString;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::UNIX_EPOCH;
+use glam::{Mat4, Vec3};
+use log::{info, warn};
+use memmap2::Mmap;
+use crate::atomic_write::write_atomically;
+use crate::mesh::{MeshOptions, NormalSource, UpAxis};
+use crate::scene::{load_scene, Bounds, Primitive, Scene, SceneVertex};
+
+const MAGIC: &[u8; 8] = b"VKPGMESH";
+// bump when the layout below changes, older files are then rebuilt
+const VERSION: u32 = 1;
+const EXTENSION: &str = "vkpgmesh";
+
+// all little endian: magic, version, normals, up axis, 2 bytes padding, scale, source length, source modification time,
+// source hash, root transform, bounds minimum and maximum, vertex count and index count
+const HEADER_SIZE: usize = 8 + 4 + 4 + 4 + 8 + 8 + 8 + 16 * 4 + 6 * 4 + 8 + 8;
+// of the source modification time, which is refreshed when only the time changed
+const MODIFIED_OFFSET: usize = 8 + 4 + 4 + 4 + 8;
+// followed by the interleaved vertices: position, normal, tex_coord and color, 12 floats each; then the u32 indices
+const VERTEX_SIZE: usize = 12 * 4;
+// sources up to this size are hashed on every load, so an edit that keeps the size and time is noticed as well;
+// larger ones are only hashed when the time changed
+const ALWAYS_HASHED_SIZE: u64 = 16 * 1024 * 1024;
+
+// the sections are used as they are in memory, see cast_section
+const _: () = assert!(size_of::<SceneVertex>() == VERTEX_SIZE);
+
+// where cache files are looked for and written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MeshCacheMode {
+    // as <file name>.vkpgmesh next to the source, or in the cache dir when that directory is not writable
+    #[default]
+    NextToSource,
+    // only in the user cache dir, named after a hash of the source path
+    CacheDir,
+    Off,
+}
+
+// what the source file looked like when the cache was written; the hash decides when only the time differs,
+// so touching a file does not throw its cache away
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct SourceStamp {
+    len: u64,
+    // nanoseconds since the Unix epoch, 0 when the file system does not record it
+    modified: u64,
+    hash: u64,
+}
+
+// the parts of the header that decide whether a cache file can be used
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct CacheKey {
+    normals: Option<NormalSource>,
+    up: UpAxis,
+    scale: f32,
+}
+
+// reads little endian values front to back
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl MeshCacheMode {
+    pub const ALL: [MeshCacheMode; 3] = [MeshCacheMode::NextToSource, MeshCacheMode::CacheDir, MeshCacheMode::Off];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MeshCacheMode::NextToSource => "next",
+            MeshCacheMode::CacheDir => "dir",
+            MeshCacheMode::Off => "off",
+        }
+    }
+}
+
+impl Display for MeshCacheMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for MeshCacheMode {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        MeshCacheMode::ALL.into_iter()
+            .find(|mode| mode.name().eq_ignore_ascii_case(value))
+            .ok_or_else(|| format!("unknown mesh cache mode \"{value}\", expected next, dir or off"))
+    }
+}
+
+impl CacheKey {
+    fn new(options: &MeshOptions) -> Self {
+        CacheKey {
+            normals: options.normals,
+            up: options.up,
+            scale: options.scale,
+        }
+    }
+
+    fn normals_byte(self) -> u8 {
+        match self.normals {
+            None => 0,
+            Some(NormalSource::Face) => 1,
+            Some(NormalSource::Vertex) => 2,
+            Some(NormalSource::File) => 3,
+        }
+    }
+
+    fn up_byte(self) -> u8 {
+        match self.up {
+            UpAxis::Y => 0,
+            UpAxis::Z => 1,
+        }
+    }
+}
+
+impl ByteReader<'_> {
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let bytes = self.bytes[self.position..self.position + N].try_into().unwrap();
+        self.position += N;
+        bytes
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.take())
+    }
+
+    fn f32(&mut self) -> f32 {
+        f32::from_le_bytes(self.take())
+    }
+
+    fn f32s<const N: usize>(&mut self) -> [f32; N] {
+        [(); N].map(|_| self.f32())
+    }
+}
+
+// like load_scene, but reads scenes with a single primitive from a cache file when one was written for the current
+// contents of the file and the same options, and writes one otherwise; cache problems are logged, never returned
+pub fn load_scene_cached(path: &Path, options: &MeshOptions, mode: MeshCacheMode) -> Result<Scene, String> {
+    // glTF is binary already, and the hash of a .gltf file would not cover the buffers and images next to it
+    let is_gltf = path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("gltf") || extension.eq_ignore_ascii_case("glb"));
+    let cache_paths = cache_paths(path, mode);
+    // the cache is little endian and mapped as it is
+    if is_gltf || cache_paths.is_empty() || cfg!(target_endian = "big") {
+        return load_scene(path, options);
+    }
+
+    let key = CacheKey::new(options);
+    let mut source_hash = None;
+    for cache_path in &cache_paths {
+        match read_cache(cache_path, path, key, &mut source_hash) {
+            Ok(Some(scene)) => return Ok(scene),
+            Ok(None) => {}
+            Err(error) => warn!("Ignoring mesh cache {}: {}", cache_path.display(), error),
+        }
+    }
+
+    let scene = load_scene(path, options)?;
+    let Some((primitive, transform)) = single_primitive(&scene) else {
+        return Ok(scene);
+    };
+
+    let stamp = match source_stamp(path, source_hash) {
+        Ok(stamp) => stamp,
+        Err(error) => {
+            warn!("Not caching {}: {}", path.display(), error);
+            return Ok(scene);
+        }
+    };
+    let bytes = cache_bytes(key, stamp, transform, primitive);
+    // the first location that can be written to
+    let written = cache_paths.iter().find(|cache_path| match write_atomically(cache_path, &bytes) {
+        Ok(()) => true,
+        Err(error) => {
+            warn!("Failed to write mesh cache {}: {}", cache_path.display(), error);
+            false
+        }
+    });
+    if let Some(cache_path) = written {
+        info!("Wrote mesh cache {} ({} bytes)", cache_path.display(), bytes.len());
+    }
+
+    Ok(scene)
+}
+
+fn cache_paths(source: &Path, mode: MeshCacheMode) -> Vec<PathBuf> {
+    let next_to_source = source.file_name().map(|file_name| {
+        let mut cache_file_name = OsString::from(file_name);
+        cache_file_name.push(format!(".{EXTENSION}"));
+        source.with_file_name(cache_file_name)
+    });
+    let in_cache_dir = dirs::cache_dir().map(|cache_dir| {
+        let source = fs::canonicalize(source).unwrap_or_else(|_| source.to_path_buf());
+        let file_name = format!("{:016x}.{EXTENSION}", fnv1a(source.as_os_str().as_encoded_bytes()));
+        cache_dir.join("vulkan_playground").join("meshes").join(file_name)
+    });
+
+    match mode {
+        MeshCacheMode::NextToSource => next_to_source.into_iter().chain(in_cache_dir).collect(),
+        MeshCacheMode::CacheDir => in_cache_dir.into_iter().collect(),
+        MeshCacheMode::Off => Vec::new(),
+    }
+}
+
+// what OBJ, PLY and STL files import to: one node with one mesh with one primitive, without materials or cameras
+fn single_primitive(scene: &Scene) -> Option<(&Primitive, Mat4)> {
+    let [node] = scene.nodes.as_slice() else {
+        return None;
+    };
+    let [mesh] = scene.meshes.as_slice() else {
+        return None;
+    };
+    let [primitive] = mesh.primitives.as_slice() else {
+        return None;
+    };
+
+    let single = scene.root_nodes == [0]
+        && node.mesh == Some(0)
+        && node.camera.is_none()
+        && node.children.is_empty()
+        && primitive.material.is_none()
+        && scene.materials.is_empty()
+        && scene.textures.is_empty()
+        && scene.cameras.is_empty();
+    single.then_some((primitive, node.transform))
+}
+
+// Ok(None) when there is no cache file or it is out of date; the source hash is computed when the modification time
+// differs or the source is small, and then kept for the other cache paths
+fn read_cache(cache_path: &Path, source: &Path, key: CacheKey, source_hash: &mut Option<u64>
+) -> Result<Option<Scene>, String> {
+    let file = match File::open(cache_path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error.to_string()),
+    };
+    // cache files are only ever replaced by renaming, never written in place, so the mapping does not change under us
+    let bytes = unsafe { Mmap::map(&file) }.map_err(|error| error.to_string())?;
+
+    if bytes.len() < HEADER_SIZE || &bytes[..MAGIC.len()] != MAGIC {
+        return Err("not a mesh cache file".to_string());
+    }
+    let mut reader = ByteReader { bytes: &bytes, position: MAGIC.len() };
+    if reader.u32() != VERSION {
+        return Ok(None);
+    }
+
+    let options = reader.take::<4>();
+    let scale = reader.f32();
+    if [options[0], options[1]] != [key.normals_byte(), key.up_byte()] || scale.to_bits() != key.scale.to_bits() {
+        info!("Mesh cache {} was written with other options, rebuilding it", cache_path.display());
+        return Ok(None);
+    }
+
+    let cached_stamp = SourceStamp {
+        len: reader.u64(),
+        modified: reader.u64(),
+        hash: reader.u64(),
+    };
+    let metadata = fs::metadata(source).map_err(|error| error.to_string())?;
+    if metadata.len() != cached_stamp.len {
+        return Ok(None);
+    }
+    let modified = modified_nanos(&metadata);
+    let touched = modified != cached_stamp.modified;
+    if touched || metadata.len() <= ALWAYS_HASHED_SIZE {
+        let hash = match *source_hash {
+            Some(hash) => hash,
+            None => *source_hash.insert(hash_file(source).map_err(|error| error.to_string())?),
+        };
+        if hash != cached_stamp.hash {
+            return Ok(None);
+        }
+    }
+
+    let transform = Mat4::from_cols_array(&reader.f32s());
+    let bounds = Bounds {
+        min: Vec3::from_array(reader.f32s()),
+        max: Vec3::from_array(reader.f32s()),
+    };
+    let vertex_count = reader.u64() as usize;
+    let index_count = reader.u64() as usize;
+
+    let expected_len = vertex_count.checked_mul(VERTEX_SIZE)
+        .zip(index_count.checked_mul(4))
+        .and_then(|(vertices, indices)| vertices.checked_add(indices)?.checked_add(HEADER_SIZE));
+    if expected_len != Some(bytes.len()) {
+        return Err("truncated".to_string());
+    }
+
+    let (vertex_bytes, index_bytes) = bytes[HEADER_SIZE..].split_at(vertex_count * VERTEX_SIZE);
+    // SAFETY: both are plain little endian floats and integers, valid for any bits, and the host is little endian
+    let (vertices, indices) = unsafe { (cast_section::<SceneVertex>(vertex_bytes), cast_section::<u32>(index_bytes)) };
+    let (Some(vertices), Some(indices)) = (vertices, indices) else {
+        return Err("misaligned".to_string());
+    };
+    if indices.iter().any(|&index| index as usize >= vertex_count) {
+        return Err("an index is out of range".to_string());
+    }
+
+    info!("Loaded mesh cache {} with bounds {} to {}", cache_path.display(), bounds.min, bounds.max);
+    // the contents are the same, so the next load does not have to hash a large source again
+    if touched && let Err(error) = write_with_modified(cache_path, &bytes, modified) {
+        warn!("Failed to update the source time in mesh cache {}: {}", cache_path.display(), error);
+    }
+    let mut scene = Scene::from_primitive(Primitive {
+        vertices: vertices.to_vec(),
+        indices: indices.to_vec(),
+        material: None,
+    });
+    scene.nodes[0].transform = transform;
+    Ok(Some(scene))
+}
+
+// None when the bytes are not aligned for T or not a whole number of them.
+// Safety: every bit pattern has to be a valid T, and the bytes have to be in the byte order of the host
+unsafe fn cast_section<T>(bytes: &[u8]) -> Option<&[T]> {
+    let (prefix, section, suffix) = unsafe { bytes.align_to::<T>() };
+    (prefix.is_empty() && suffix.is_empty()).then_some(section)
+}
+
+fn cache_bytes(key: CacheKey, stamp: SourceStamp, transform: Mat4, primitive: &Primitive) -> Vec<u8> {
+    let bounds = primitive.bounds().unwrap_or(Bounds { min: Vec3::ZERO, max: Vec3::ZERO });
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + primitive.vertices.len() * VERTEX_SIZE + primitive.indices.len() * 4);
+
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&[key.normals_byte(), key.up_byte(), 0, 0]);
+    bytes.extend_from_slice(&key.scale.to_le_bytes());
+    for value in [stamp.len, stamp.modified, stamp.hash] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    let floats = transform.to_cols_array().into_iter()
+        .chain(bounds.min.to_array())
+        .chain(bounds.max.to_array());
+    for value in floats {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    for count in [primitive.vertices.len(), primitive.indices.len()] {
+        bytes.extend_from_slice(&(count as u64).to_le_bytes());
+    }
+
+    for vertex in &primitive.vertices {
+        let floats = vertex.position.iter()
+            .chain(&vertex.normal)
+            .chain(&vertex.tex_coord)
+            .chain(&vertex.color);
+        for value in floats {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    for index in &primitive.indices {
+        bytes.extend_from_slice(&index.to_le_bytes());
+    }
+    bytes
+}
+
+// replaces the whole file, other processes may have the old one mapped
+fn write_with_modified(cache_path: &Path, bytes: &[u8], modified: u64) -> io::Result<()> {
+    let mut bytes = bytes.to_vec();
+    bytes[MODIFIED_OFFSET..MODIFIED_OFFSET + 8].copy_from_slice(&modified.to_le_bytes());
+    write_atomically(cache_path, &bytes)
+}
+
+fn source_stamp(path: &Path, hash: Option<u64>) -> io::Result<SourceStamp> {
+    let metadata = fs::metadata(path)?;
+    Ok(SourceStamp {
+        len: metadata.len(),
+        modified: modified_nanos(&metadata),
+        hash: match hash {
+            Some(hash) => hash,
+            None => hash_file(path)?,
+        },
+    })
+}
+
+fn modified_nanos(metadata: &fs::Metadata) -> u64 {
+    metadata.modified().ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_nanos() as u64)
+}
+
+fn hash_file(path: &Path) -> io::Result<u64> {
+    Ok(fnv1a(&fs::read(path)?))
+}
+
+// FNV-1a, stable across Rust versions unlike the std hashers
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
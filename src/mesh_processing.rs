@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use glam::Vec3;
+use crate::scene::{Primitive, SceneVertex};
+
+// how normals are generated from the triangles of a mesh
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NormalMode {
+    // the normal of the triangle at each of its corners, so every edge is hard
+    Flat,
+    // average of the triangles around a position, weighted by their area
+    AreaWeighted,
+    // average weighted by the angle of each triangle at the position, so it does not depend on the triangulation
+    AngleWeighted,
+    // angle weighted, but only over the triangles whose normal is within angle_degrees of the one of the corner,
+    // so edges sharper than that stay hard
+    Crease { angle_degrees: f32 },
+}
+
+impl NormalMode {
+    pub const DEFAULT_CREASE_ANGLE: f32 = 30.0;
+
+    pub fn name(self) -> &'static str {
+        match self {
+            NormalMode::Flat => "Flat",
+            NormalMode::AreaWeighted => "Area weighted",
+            NormalMode::AngleWeighted => "Angle weighted",
+            NormalMode::Crease { .. } => "Crease angle",
+        }
+    }
+}
+
+impl Display for NormalMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NormalMode::Crease { angle_degrees } => write!(f, "{} {angle_degrees}°", self.name()),
+            _ => f.write_str(self.name()),
+        }
+    }
+}
+
+// one unit normal per index; corners with bit-identical positions are smoothed together even when their indices differ,
+// so meshes split at texture seams or into loose triangles get smooth normals as well
+pub fn corner_normals(positions: &[Vec3], indices: &[u32], mode: NormalMode) -> Vec<Vec3> {
+    let face_normals = indices.chunks_exact(3)
+        .map(|triangle| weighted_face_normal(positions, triangle))
+        .collect::<Vec<_>>();
+    let unit_face_normals = face_normals.iter().map(|normal| normal.normalize_or_zero()).collect::<Vec<_>>();
+    if mode == NormalMode::Flat {
+        return unit_face_normals.iter().flat_map(|&normal| [normal; 3]).collect();
+    }
+
+    // the welded position of every corner
+    let mut position_ids = HashMap::new();
+    let corner_positions = indices.iter()
+        .map(|&index| {
+            let next_id = position_ids.len();
+            *position_ids.entry(positions[index as usize].to_array().map(f32::to_bits)).or_insert(next_id)
+        })
+        .collect::<Vec<_>>();
+
+    // what every corner adds to the normals at its position
+    let contributions = indices.chunks_exact(3)
+        .enumerate()
+        .flat_map(|(triangle_index, triangle)| (0..3).map(move |corner| (triangle_index, triangle, corner)))
+        .map(|(triangle_index, triangle, corner)| match mode {
+            NormalMode::AreaWeighted => face_normals[triangle_index],
+            _ => unit_face_normals[triangle_index] * corner_angle(positions, triangle, corner),
+        })
+        .collect::<Vec<_>>();
+
+    if let NormalMode::Crease { angle_degrees } = mode {
+        let min_cos = angle_degrees.to_radians().cos();
+        let mut position_corners = vec![Vec::new(); position_ids.len()];
+        for (corner, &position) in corner_positions.iter().enumerate() {
+            position_corners[position].push(corner);
+        }
+
+        return (0..indices.len())
+            .map(|corner| {
+                let triangle = corner / 3;
+                position_corners[corner_positions[corner]].iter()
+                    // the own triangle always counts, rounding could leave it out at an angle of 0
+                    .filter(|&&other| other / 3 == triangle
+                        || unit_face_normals[triangle].dot(unit_face_normals[other / 3]) >= min_cos)
+                    .map(|&other| contributions[other])
+                    .sum::<Vec3>()
+                    .normalize_or_zero()
+            })
+            .collect();
+    }
+
+    let mut normals = vec![Vec3::ZERO; position_ids.len()];
+    for (&position, &contribution) in corner_positions.iter().zip(&contributions) {
+        normals[position] += contribution;
+    }
+    corner_positions.iter().map(|&position| normals[position].normalize_or_zero()).collect()
+}
+
+// replaces the normals of the primitive; vertices are split where their corners get different normals
+// and merged where they end up the same
+pub fn generate_normals(primitive: &mut Primitive, mode: NormalMode) {
+    let positions = primitive.vertices.iter()
+        .map(|vertex| Vec3::from_array(vertex.position))
+        .collect::<Vec<_>>();
+    let normals = corner_normals(&positions, &primitive.indices, mode);
+
+    let mut vertices = Vec::new();
+    let mut vertex_indices = HashMap::new();
+    primitive.indices = primitive.indices.iter()
+        .zip(normals)
+        .map(|(&index, normal)| {
+            let vertex = SceneVertex {
+                normal: normal.to_array(),
+                ..primitive.vertices[index as usize]
+            };
+            *vertex_indices.entry(vertex_key(&vertex)).or_insert_with(|| {
+                vertices.push(vertex);
+                vertices.len() as u32 - 1
+            })
+        })
+        .collect();
+    primitive.vertices = vertices;
+}
+
+// not normalized, the length is twice the triangle area
+fn weighted_face_normal(positions: &[Vec3], triangle: &[u32]) -> Vec3 {
+    let [a, b, c] = [0, 1, 2].map(|i| positions[triangle[i] as usize]);
+    (b - a).cross(c - a)
+}
+
+// in radians, degenerate corners give a quarter turn but their triangle has no normal to weight
+fn corner_angle(positions: &[Vec3], triangle: &[u32], corner: usize) -> f32 {
+    let [at, next, previous] = [0, 1, 2].map(|offset| positions[triangle[(corner + offset) % 3] as usize]);
+    let (to_next, to_previous) = ((next - at).normalize_or_zero(), (previous - at).normalize_or_zero());
+    to_next.dot(to_previous).clamp(-1.0, 1.0).acos()
+}
+
+fn vertex_key(vertex: &SceneVertex) -> [u32; 12] {
+    let mut key = [0; 12];
+    let values = vertex.position.iter()
+        .chain(&vertex.normal)
+        .chain(&vertex.tex_coord)
+        .chain(&vertex.color);
+    for (bits, value) in key.iter_mut().zip(values) {
+        *bits = value.to_bits();
+    }
+    key
+}
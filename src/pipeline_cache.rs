@@ -0,0 +1,152 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use log::{info, warn};
+use vulkano::device::Device;
+use vulkano::pipeline::cache::{PipelineCache, PipelineCacheCreateInfo};
+use crate::atomic_write::write_atomically;
+use crate::format_uuid;
+
+const MAGIC: &[u8; 8] = b"VKPGPC01";
+const HEADER_SIZE: usize = 8 + 16 + 4 + 4 + 4 + 16 + 8;
+// VkPipelineCacheHeaderVersionOne, at the start of the data the driver returns
+const VULKAN_HEADER_SIZE: usize = 4 + 4 + 4 + 4 + 16;
+
+// identifies the device and driver a cache file was written by; the driver would reject data from another
+// device or driver version itself, but not every driver does so gracefully
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct CacheKey {
+    device_uuid: [u8; 16],
+    driver_version: u32,
+    vendor_id: u32,
+    device_id: u32,
+    pipeline_cache_uuid: [u8; 16],
+}
+
+impl CacheKey {
+    fn for_device(device: &Device) -> Self {
+        let properties = device.physical_device().properties();
+        CacheKey {
+            // devices without Vulkan 1.1 have no UUID, the pipeline cache UUID still tells them apart
+            device_uuid: properties.device_uuid.unwrap_or_default(),
+            driver_version: properties.driver_version,
+            vendor_id: properties.vendor_id,
+            device_id: properties.device_id,
+            pipeline_cache_uuid: properties.pipeline_cache_uuid,
+        }
+    }
+
+    fn file_name(&self) -> String {
+        let uuid = if self.device_uuid == [0; 16] { self.pipeline_cache_uuid } else { self.device_uuid };
+        format!("pipeline_cache_{}_{:08x}.bin", format_uuid(&uuid), self.driver_version)
+    }
+
+    fn to_bytes(self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&self.device_uuid);
+        bytes.extend_from_slice(&self.driver_version.to_le_bytes());
+        bytes.extend_from_slice(&self.vendor_id.to_le_bytes());
+        bytes.extend_from_slice(&self.device_id.to_le_bytes());
+        bytes.extend_from_slice(&self.pipeline_cache_uuid);
+        bytes
+    }
+
+    // checks the header the driver itself puts in front of its data
+    fn matches_vulkan_header(&self, data: &[u8]) -> bool {
+        if data.len() < VULKAN_HEADER_SIZE {
+            return false;
+        }
+        let read_u32 = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+
+        read_u32(0) as usize >= VULKAN_HEADER_SIZE
+            && read_u32(4) == 1
+            && read_u32(8) == self.vendor_id
+            && read_u32(12) == self.device_id
+            && data[16..32] == self.pipeline_cache_uuid
+    }
+}
+
+pub(crate) fn cache_file_path(device: &Device) -> Option<PathBuf> {
+    let cache_dir = dirs::cache_dir()?;
+    Some(cache_dir.join("vulkan_playground").join(CacheKey::for_device(device).file_name()))
+}
+
+// starts with an empty cache when the file is missing, unreadable or written for another device or driver;
+// None when the driver cannot create even an empty one, pipelines are then built without a cache
+pub(crate) fn load_pipeline_cache(device: Arc<Device>, path: Option<&Path>) -> Option<Arc<PipelineCache>> {
+    let key = CacheKey::for_device(&device);
+    let initial_data = path
+        .and_then(|path| match fs::read(path) {
+            Ok(bytes) => Some((path, bytes)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+            Err(error) => {
+                warn!("Failed to read pipeline cache {}: {}", path.display(), error);
+                None
+            }
+        })
+        .and_then(|(path, bytes)| match parse_cache_file(&key, &bytes) {
+            Ok(data) => {
+                info!("Loaded pipeline cache {} ({} bytes)", path.display(), data.len());
+                Some(data.to_vec())
+            }
+            Err(reason) => {
+                warn!("Ignoring pipeline cache {}: {}", path.display(), reason);
+                None
+            }
+        })
+        .unwrap_or_default();
+
+    if !initial_data.is_empty() {
+        // the data has been checked against this device, so the driver only has to validate its own contents
+        let cache = unsafe {
+            PipelineCache::new(device.clone(), PipelineCacheCreateInfo {
+                initial_data,
+                ..Default::default()
+            })
+        };
+        match cache {
+            Ok(cache) => return Some(cache),
+            Err(error) => warn!("Driver rejected pipeline cache data, starting empty: {}", error),
+        }
+    }
+
+    unsafe {
+        PipelineCache::new(device, PipelineCacheCreateInfo::default())
+    }
+        .inspect_err(|error| warn!("Failed to create pipeline cache, continuing without one: {}", error))
+        .ok()
+}
+
+pub(crate) fn save_pipeline_cache(cache: &PipelineCache, device: &Device, path: &Path) -> io::Result<()> {
+    let data = cache.get_data().map_err(io::Error::other)?;
+
+    let mut bytes = CacheKey::for_device(device).to_bytes();
+    bytes.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&data);
+
+    write_atomically(path, &bytes)
+}
+
+fn parse_cache_file<'a>(key: &CacheKey, bytes: &'a [u8]) -> Result<&'a [u8], &'static str> {
+    if bytes.len() < HEADER_SIZE || &bytes[..MAGIC.len()] != MAGIC {
+        return Err("not a pipeline cache file of this version");
+    }
+
+    let (header, data) = bytes.split_at(HEADER_SIZE);
+    if header[..HEADER_SIZE - 8] != key.to_bytes() {
+        return Err("written for another device or driver version");
+    }
+
+    let data_len = u64::from_le_bytes(header[HEADER_SIZE - 8..].try_into().unwrap());
+    if data_len != data.len() as u64 {
+        return Err("truncated");
+    }
+
+    if !key.matches_vulkan_header(data) {
+        return Err("driver header does not match this device");
+    }
+
+    Ok(data)
+}
@@ -0,0 +1,339 @@
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use crate::mesh::NormalSource;
+use crate::mesh_processing::{generate_normals, NormalMode};
+use crate::scene::{Primitive, SceneVertex};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Encoding {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ScalarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+#[derive(Clone, Debug)]
+enum PropertyType {
+    Scalar(ScalarType),
+    List { count_type: ScalarType, item_type: ScalarType },
+}
+
+#[derive(Clone, Debug)]
+struct Property {
+    name: String,
+    property_type: PropertyType,
+}
+
+#[derive(Clone, Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+// reads values one at a time from the body, in either encoding
+struct BodyReader<'a> {
+    encoding: Encoding,
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl FromStr for ScalarType {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(match value {
+            "char" | "int8" => ScalarType::Int8,
+            "uchar" | "uint8" => ScalarType::UInt8,
+            "short" | "int16" => ScalarType::Int16,
+            "ushort" | "uint16" => ScalarType::UInt16,
+            "int" | "int32" => ScalarType::Int32,
+            "uint" | "uint32" => ScalarType::UInt32,
+            "float" | "float32" => ScalarType::Float32,
+            "double" | "float64" => ScalarType::Float64,
+            _ => return Err(format!("unknown property type {value}")),
+        })
+    }
+}
+
+impl ScalarType {
+    fn size(self) -> usize {
+        match self {
+            ScalarType::Int8 | ScalarType::UInt8 => 1,
+            ScalarType::Int16 | ScalarType::UInt16 => 2,
+            ScalarType::Int32 | ScalarType::UInt32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        }
+    }
+
+    // colors stored as integers are scaled to 0..1 by the maximum of their type
+    fn color_scale(self) -> f64 {
+        match self {
+            ScalarType::UInt8 => u8::MAX as f64,
+            ScalarType::UInt16 => u16::MAX as f64,
+            _ => 1.0,
+        }
+    }
+}
+
+impl BodyReader<'_> {
+    fn read(&mut self, scalar_type: ScalarType) -> Result<f64, String> {
+        if self.encoding == Encoding::Ascii {
+            return self.read_ascii();
+        }
+
+        let size = scalar_type.size();
+        let bytes = self.bytes.get(self.position..self.position + size)
+            .ok_or("unexpected end of file")?;
+        self.position += size;
+
+        let mut buffer = [0u8; 8];
+        buffer[..size].copy_from_slice(bytes);
+        if self.encoding == Encoding::BinaryBigEndian {
+            buffer[..size].reverse();
+        }
+
+        Ok(match scalar_type {
+            ScalarType::Int8 => buffer[0] as i8 as f64,
+            ScalarType::UInt8 => buffer[0] as f64,
+            ScalarType::Int16 => i16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+            ScalarType::UInt16 => u16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+            ScalarType::Int32 => i32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+            ScalarType::UInt32 => u32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+            ScalarType::Float32 => f32::from_le_bytes(buffer[..4].try_into().unwrap()) as f64,
+            ScalarType::Float64 => f64::from_le_bytes(buffer),
+        })
+    }
+
+    // every element takes at least a byte of the body, so the counts in the header can not preallocate more than
+    // the file could fill
+    fn capacity_for(&self, count: usize) -> usize {
+        count.min(self.bytes.len() - self.position)
+    }
+
+    fn read_ascii(&mut self) -> Result<f64, String> {
+        let rest = &self.bytes[self.position..];
+        let start = rest.iter().position(|byte| !byte.is_ascii_whitespace()).ok_or("unexpected end of file")?;
+        let length = rest[start..].iter().position(|byte| byte.is_ascii_whitespace()).unwrap_or(rest.len() - start);
+        self.position += start + length;
+
+        let token = std::str::from_utf8(&rest[start..start + length]).map_err(|error| error.to_string())?;
+        token.parse().map_err(|_| format!("invalid number {token}"))
+    }
+}
+
+// vertices need x, y and z; nx, ny, nz, red, green, blue, alpha, u and v (or s and t) are used when present;
+// faces need a vertex_indices (or vertex_index) list, polygons are triangulated as fans
+pub(crate) fn load_ply(path: &Path, normals: Option<NormalSource>) -> Result<Primitive, String> {
+    let bytes = fs::read(path).map_err(|error| format!("Failed to open {}: {error}", path.display()))?;
+    parse_ply(&bytes, normals).map_err(|error| format!("Failed to parse {}: {error}", path.display()))
+}
+
+fn parse_ply(bytes: &[u8], normal_source: Option<NormalSource>) -> Result<Primitive, String> {
+    let (encoding, elements, body_start) = parse_header(bytes)?;
+    let mut reader = BodyReader {
+        encoding,
+        bytes,
+        position: body_start,
+    };
+
+    let mut primitive = Primitive::default();
+    let mut has_normals = false;
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => {
+                has_normals = ["nx", "ny", "nz"].iter()
+                    .all(|name| element.properties.iter().any(|property| property.name == *name));
+                primitive.vertices = read_vertices(&mut reader, element)?;
+            }
+            "face" => primitive.indices = read_faces(&mut reader, element)?,
+            _ => skip_element(&mut reader, element)?,
+        }
+    }
+
+    if primitive.indices.is_empty() {
+        return Err("no faces".to_string());
+    }
+    if primitive.indices.iter().any(|&index| index as usize >= primitive.vertices.len()) {
+        return Err("a face references a vertex that does not exist".to_string());
+    }
+
+    // shared vertices are the norm for scans, so they default to smooth normals
+    match normal_source.unwrap_or(if has_normals { NormalSource::File } else { NormalSource::Vertex }) {
+        NormalSource::Face => generate_normals(&mut primitive, NormalMode::Flat),
+        NormalSource::Vertex => generate_normals(&mut primitive, NormalMode::AreaWeighted),
+        NormalSource::File if has_normals => {}
+        NormalSource::File => return Err("the vertices have no normals".to_string()),
+    }
+
+    Ok(primitive)
+}
+
+fn parse_header(bytes: &[u8]) -> Result<(Encoding, Vec<Element>, usize), String> {
+    const END_HEADER: &[u8] = b"end_header";
+    let end = bytes.windows(END_HEADER.len())
+        .position(|window| window == END_HEADER)
+        .ok_or("no end_header")?;
+    // the body starts after the line break that ends end_header
+    let body_start = bytes[end..].iter().position(|&byte| byte == b'\n').map(|offset| end + offset + 1)
+        .ok_or("no body")?;
+
+    let header = std::str::from_utf8(&bytes[..end]).map_err(|_| "header is not text")?;
+    let mut lines = header.lines().map(str::trim);
+    if lines.next() != Some("ply") {
+        return Err("not a PLY file".to_string());
+    }
+
+    let mut encoding = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in lines {
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match words.as_slice() {
+            ["format", format, _version] => {
+                encoding = Some(match *format {
+                    "ascii" => Encoding::Ascii,
+                    "binary_little_endian" => Encoding::BinaryLittleEndian,
+                    "binary_big_endian" => Encoding::BinaryBigEndian,
+                    _ => return Err(format!("unknown format {format}")),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| format!("invalid element count {count}"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count_type, item_type, name] => elements.last_mut()
+                .ok_or("property before the first element")?
+                .properties.push(Property {
+                    name: name.to_string(),
+                    property_type: PropertyType::List {
+                        count_type: count_type.parse()?,
+                        item_type: item_type.parse()?,
+                    },
+                }),
+            ["property", scalar_type, name] => elements.last_mut()
+                .ok_or("property before the first element")?
+                .properties.push(Property {
+                    name: name.to_string(),
+                    property_type: PropertyType::Scalar(scalar_type.parse()?),
+                }),
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(format!("unexpected header line \"{line}\"")),
+        }
+    }
+
+    Ok((encoding.ok_or("no format")?, elements, body_start))
+}
+
+fn read_vertices(reader: &mut BodyReader, element: &Element) -> Result<Vec<SceneVertex>, String> {
+    let mut vertices = Vec::with_capacity(reader.capacity_for(element.count));
+    for _ in 0..element.count {
+        let mut vertex = SceneVertex::default();
+        for property in &element.properties {
+            let PropertyType::Scalar(scalar_type) = property.property_type else {
+                skip_property(reader, property)?;
+                continue;
+            };
+            let value = reader.read(scalar_type)?;
+            let color = (value / scalar_type.color_scale()) as f32;
+
+            match property.name.as_str() {
+                "x" => vertex.position[0] = value as f32,
+                "y" => vertex.position[1] = value as f32,
+                "z" => vertex.position[2] = value as f32,
+                "nx" => vertex.normal[0] = value as f32,
+                "ny" => vertex.normal[1] = value as f32,
+                "nz" => vertex.normal[2] = value as f32,
+                "u" | "s" | "texture_u" => vertex.tex_coord[0] = value as f32,
+                "v" | "t" | "texture_v" => vertex.tex_coord[1] = value as f32,
+                // PLY colors are sRGB, the shader expects linear ones
+                "red" => vertex.color[0] = srgb_to_linear(color),
+                "green" => vertex.color[1] = srgb_to_linear(color),
+                "blue" => vertex.color[2] = srgb_to_linear(color),
+                "alpha" => vertex.color[3] = color,
+                _ => {}
+            }
+        }
+        vertices.push(vertex);
+    }
+    Ok(vertices)
+}
+
+fn read_faces(reader: &mut BodyReader, element: &Element) -> Result<Vec<u32>, String> {
+    let index_count = element.count.checked_mul(3).ok_or("too many faces")?;
+    let mut indices = Vec::with_capacity(reader.capacity_for(index_count));
+    for _ in 0..element.count {
+        for property in &element.properties {
+            let PropertyType::List { count_type, item_type } = property.property_type else {
+                skip_property(reader, property)?;
+                continue;
+            };
+            if property.name != "vertex_indices" && property.name != "vertex_index" {
+                skip_property(reader, property)?;
+                continue;
+            }
+
+            let count = reader.read(count_type)? as usize;
+            let polygon = (0..count)
+                .map(|_| reader.read(item_type).and_then(vertex_index))
+                .collect::<Result<Vec<_>, _>>()?;
+            for i in 1..polygon.len().saturating_sub(1) {
+                indices.extend_from_slice(&[polygon[0], polygon[i], polygon[i + 1]]);
+            }
+        }
+    }
+    Ok(indices)
+}
+
+// the range against the vertex count is checked once all elements are read
+fn vertex_index(value: f64) -> Result<u32, String> {
+    if value >= 0.0 && value <= u32::MAX as f64 && value.fract() == 0.0 {
+        Ok(value as u32)
+    } else {
+        Err(format!("invalid vertex index {value}"))
+    }
+}
+
+fn skip_element(reader: &mut BodyReader, element: &Element) -> Result<(), String> {
+    for _ in 0..element.count {
+        for property in &element.properties {
+            skip_property(reader, property)?;
+        }
+    }
+    Ok(())
+}
+
+fn skip_property(reader: &mut BodyReader, property: &Property) -> Result<(), String> {
+    match property.property_type {
+        PropertyType::Scalar(scalar_type) => {
+            reader.read(scalar_type)?;
+        }
+        PropertyType::List { count_type, item_type } => {
+            let count = reader.read(count_type)? as usize;
+            for _ in 0..count {
+                reader.read(item_type)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
@@ -0,0 +1,130 @@
+use vulkano::device::QueueFlags;
+use crate::QueueFamilyCandidate;
+
+// roles may share a family, in which case they also share the queue
+pub(crate) struct QueueFamilySelection {
+    pub primary: u32,
+    pub graphics: Option<u32>,
+    pub compute: Option<u32>,
+    pub transfer: Option<u32>,
+    pub present: Option<u32>,
+}
+
+impl QueueFamilySelection {
+    pub fn new(queue_families: &[QueueFamilyCandidate], queue_flags: QueueFlags) -> Option<Self> {
+        let find = |predicate: &dyn Fn(&QueueFamilyCandidate) -> bool| {
+            queue_families.iter()
+                .find(|queue_family| predicate(queue_family))
+                .map(|queue_family| queue_family.index)
+        };
+        // headless families have no presentation support to check
+        let can_present = |queue_family: &QueueFamilyCandidate| queue_family.presentation_support != Some(false);
+        let presents = |queue_family: &QueueFamilyCandidate| queue_family.presentation_support == Some(true);
+
+        let primary = find(&|f| f.queue_flags.contains(queue_flags) && can_present(f))?;
+
+        let graphics = find(&|f| f.queue_flags.intersects(QueueFlags::GRAPHICS) && can_present(f))
+            .or_else(|| find(&|f| f.queue_flags.intersects(QueueFlags::GRAPHICS)));
+
+        let compute = find(&|f| f.queue_flags.intersects(QueueFlags::COMPUTE)
+                && !f.queue_flags.intersects(QueueFlags::GRAPHICS))
+            .or_else(|| find(&|f| f.queue_flags.intersects(QueueFlags::COMPUTE)));
+
+        // graphics and compute families support transfers even when they do not report it
+        let transfer = find(&|f| f.queue_flags.intersects(QueueFlags::TRANSFER)
+                && !f.queue_flags.intersects(QueueFlags::GRAPHICS | QueueFlags::COMPUTE))
+            .or_else(|| find(&|f| f.queue_flags.intersects(QueueFlags::TRANSFER)
+                && !f.queue_flags.intersects(QueueFlags::GRAPHICS)))
+            .or_else(|| find(&|f| f.queue_flags.intersects(QueueFlags::TRANSFER | QueueFlags::GRAPHICS | QueueFlags::COMPUTE)));
+
+        let present = graphics
+            .filter(|index| queue_families.iter().any(|f| f.index == *index && presents(f)))
+            .or_else(|| find(&presents));
+
+        Some(QueueFamilySelection {
+            primary,
+            graphics,
+            compute,
+            transfer,
+            present,
+        })
+    }
+
+    pub fn unique_indices(&self) -> Vec<u32> {
+        let mut indices = vec![self.primary];
+        for index in [self.graphics, self.compute, self.transfer, self.present].into_iter().flatten() {
+            if !indices.contains(&index) {
+                indices.push(index);
+            }
+        }
+        indices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn family(index: u32, queue_flags: QueueFlags, presentation_support: Option<bool>) -> QueueFamilyCandidate {
+        QueueFamilyCandidate { index, queue_flags, presentation_support }
+    }
+
+    fn all() -> QueueFlags {
+        QueueFlags::GRAPHICS | QueueFlags::COMPUTE | QueueFlags::TRANSFER
+    }
+
+    fn compute() -> QueueFlags {
+        QueueFlags::COMPUTE | QueueFlags::TRANSFER
+    }
+
+    #[test]
+    fn headless_prefers_dedicated_families() {
+        let queue_families = [family(0, all(), None), family(1, compute(), None), family(2, QueueFlags::TRANSFER, None)];
+        let selection = QueueFamilySelection::new(&queue_families, QueueFlags::GRAPHICS).unwrap();
+
+        assert_eq!(selection.primary, 0);
+        assert_eq!(selection.graphics, Some(0));
+        assert_eq!(selection.compute, Some(1));
+        assert_eq!(selection.transfer, Some(2));
+        assert_eq!(selection.present, None);
+        assert_eq!(selection.unique_indices(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn headless_with_a_single_family() {
+        let selection = QueueFamilySelection::new(&[family(0, all(), None)], QueueFlags::COMPUTE).unwrap();
+
+        assert_eq!(selection.primary, 0);
+        assert_eq!(selection.compute, Some(0));
+        assert_eq!(selection.transfer, Some(0));
+        assert_eq!(selection.unique_indices(), [0]);
+    }
+
+    #[test]
+    fn missing_queue_flags() {
+        assert!(QueueFamilySelection::new(&[family(0, compute(), None)], QueueFlags::GRAPHICS).is_none());
+    }
+
+    #[test]
+    fn primary_family_has_to_present() {
+        let queue_families = [family(0, all(), Some(false)), family(1, all(), Some(true))];
+        let selection = QueueFamilySelection::new(&queue_families, QueueFlags::GRAPHICS).unwrap();
+
+        assert_eq!(selection.primary, 1);
+        assert_eq!(selection.graphics, Some(1));
+        assert_eq!(selection.present, Some(1));
+
+        let queue_families = [family(0, all(), Some(false)), family(1, compute(), Some(true))];
+        assert!(QueueFamilySelection::new(&queue_families, QueueFlags::GRAPHICS).is_none());
+    }
+
+    #[test]
+    fn separate_present_family() {
+        let queue_families = [family(0, all(), Some(false)), family(1, compute(), Some(true))];
+        let selection = QueueFamilySelection::new(&queue_families, QueueFlags::COMPUTE).unwrap();
+
+        assert_eq!(selection.primary, 1);
+        assert_eq!(selection.graphics, Some(0));
+        assert_eq!(selection.present, Some(1));
+    }
+}
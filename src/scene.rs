@@ -0,0 +1,318 @@
+use std::path::Path;
+use glam::{Mat4, Vec3};
+use vulkano::buffer::BufferContents;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use crate::{gltf_import, ply_import, stl_import};
+use crate::mesh::{load_mesh, MeshOptions, UpAxis};
+
+// what window_graphics draws: a node hierarchy referencing meshes, materials, textures and cameras by index;
+// OBJ files become a scene with a single node and mesh
+#[derive(Clone, Debug, Default)]
+pub struct Scene {
+    pub nodes: Vec<SceneNode>,
+    pub root_nodes: Vec<usize>,
+    pub meshes: Vec<SceneMesh>,
+    pub materials: Vec<Material>,
+    pub textures: Vec<Texture>,
+    pub cameras: Vec<SceneCamera>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SceneNode {
+    pub name: Option<String>,
+    // relative to the parent node
+    pub transform: Mat4,
+    pub children: Vec<usize>,
+    pub mesh: Option<usize>,
+    pub camera: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SceneMesh {
+    pub name: Option<String>,
+    pub primitives: Vec<Primitive>,
+}
+
+// triangles only, indices into vertices
+#[derive(Clone, Debug, Default)]
+pub struct Primitive {
+    pub vertices: Vec<SceneVertex>,
+    pub indices: Vec<u32>,
+    // None uses the default material
+    pub material: Option<usize>,
+}
+
+// the names have to match the vertex shader inputs
+#[derive(BufferContents, Vertex, Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct SceneVertex {
+    #[format(R32G32B32_SFLOAT)]
+    pub position: [f32; 3],
+    #[format(R32G32B32_SFLOAT)]
+    pub normal: [f32; 3],
+    #[format(R32G32_SFLOAT)]
+    pub tex_coord: [f32; 2],
+    // linear RGBA, multiplies the base color; white for formats without vertex colors
+    #[format(R32G32B32A32_SFLOAT)]
+    pub color: [f32; 4],
+}
+
+// glTF metallic-roughness; texture factors multiply the texture values
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub name: Option<String>,
+    pub base_color_factor: [f32; 4],
+    // sRGB, into textures
+    pub base_color_texture: Option<usize>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    // linear, roughness in the green and metalness in the blue channel, into textures
+    pub metallic_roughness_texture: Option<usize>,
+    pub emissive_factor: [f32; 3],
+    pub double_sided: bool,
+}
+
+// always converted to RGBA with 8 bits per channel
+#[derive(Clone, Debug)]
+pub struct Texture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SceneCamera {
+    pub name: Option<String>,
+    pub projection: Projection,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    // None for aspect_ratio uses the one of the viewport, None for zfar means an infinite far plane
+    Perspective { yfov: f32, aspect_ratio: Option<f32>, znear: f32, zfar: Option<f32> },
+    Orthographic { xmag: f32, ymag: f32, znear: f32, zfar: f32 },
+}
+
+// axis-aligned, in the space of the positions it was computed from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+// a mesh placed in the world
+#[derive(Clone, Copy, Debug)]
+pub struct MeshInstance {
+    pub mesh: usize,
+    pub world_transform: Mat4,
+}
+
+// a camera placed in the world, looking down its negative Z axis
+#[derive(Clone, Copy, Debug)]
+pub struct CameraInstance {
+    pub camera: usize,
+    pub world_transform: Mat4,
+}
+
+impl Default for SceneVertex {
+    fn default() -> Self {
+        SceneVertex {
+            position: [0.0; 3],
+            normal: [0.0; 3],
+            tex_coord: [0.0; 2],
+            color: [1.0; 4],
+        }
+    }
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            name: None,
+            base_color_factor: [1.0; 4],
+            base_color_texture: None,
+            metallic_factor: 0.0,
+            roughness_factor: 0.5,
+            metallic_roughness_texture: None,
+            emissive_factor: [0.0; 3],
+            double_sided: true,
+        }
+    }
+}
+
+impl Projection {
+    // for Vulkan: right-handed view space, depth from 0 to 1 and Y pointing down in clip space
+    pub fn matrix(&self, viewport_aspect_ratio: f32) -> Mat4 {
+        let flip_y = Mat4::from_scale(Vec3::new(1.0, -1.0, 1.0));
+        let projection = match *self {
+            Projection::Perspective { yfov, aspect_ratio, znear, zfar } => {
+                let aspect_ratio = aspect_ratio.unwrap_or(viewport_aspect_ratio);
+                match zfar {
+                    Some(zfar) => Mat4::perspective_rh(yfov, aspect_ratio, znear, zfar),
+                    None => Mat4::perspective_infinite_rh(yfov, aspect_ratio, znear),
+                }
+            }
+            Projection::Orthographic { xmag, ymag, znear, zfar } => {
+                Mat4::orthographic_rh(-xmag, xmag, -ymag, ymag, znear, zfar)
+            }
+        };
+        flip_y * projection
+    }
+}
+
+impl Bounds {
+    // None without points
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Option<Self> {
+        points.into_iter().fold(None, |bounds, point| Some(match bounds {
+            None => Bounds { min: point, max: point },
+            Some(Bounds { min, max }) => Bounds { min: min.min(point), max: max.max(point) },
+        }))
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+}
+
+impl Primitive {
+    pub fn bounds(&self) -> Option<Bounds> {
+        Bounds::from_points(self.vertices.iter().map(|vertex| Vec3::from_array(vertex.position)))
+    }
+
+    // the smallest t >= 0 for which origin + t * direction lies on a triangle, hit from either side
+    pub fn ray_intersection(&self, origin: Vec3, direction: Vec3) -> Option<f32> {
+        self.indices.chunks_exact(3)
+            .filter_map(|triangle| {
+                // Möller-Trumbore
+                let [a, b, c] = [0, 1, 2].map(|i| Vec3::from_array(self.vertices[triangle[i] as usize].position));
+                let (edge_ab, edge_ac) = (b - a, c - a);
+                let p = direction.cross(edge_ac);
+                let determinant = edge_ab.dot(p);
+                // parallel to the triangle
+                if determinant.abs() < f32::MIN_POSITIVE {
+                    return None;
+                }
+
+                let to_origin = origin - a;
+                let u = to_origin.dot(p) / determinant;
+                let q = to_origin.cross(edge_ab);
+                let v = direction.dot(q) / determinant;
+                let t = edge_ac.dot(q) / determinant;
+                (u >= 0.0 && v >= 0.0 && u + v <= 1.0 && t >= 0.0).then_some(t)
+            })
+            .min_by(f32::total_cmp)
+    }
+}
+
+impl Scene {
+    // a single node without transform
+    pub fn from_primitive(primitive: Primitive) -> Self {
+        Scene {
+            nodes: vec![SceneNode {
+                mesh: Some(0),
+                ..Default::default()
+            }],
+            root_nodes: vec![0],
+            meshes: vec![SceneMesh {
+                name: None,
+                primitives: vec![primitive],
+            }],
+            ..Default::default()
+        }
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.meshes.iter().flat_map(|mesh| &mesh.primitives).map(|primitive| primitive.vertices.len()).sum()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.meshes.iter().flat_map(|mesh| &mesh.primitives).map(|primitive| primitive.indices.len() / 3).sum()
+    }
+
+    pub fn mesh_instances(&self) -> Vec<MeshInstance> {
+        let mut mesh_instances = Vec::new();
+        self.visit_nodes(|node, world_transform| {
+            if let Some(mesh) = node.mesh {
+                mesh_instances.push(MeshInstance { mesh, world_transform });
+            }
+        });
+        mesh_instances
+    }
+
+    pub fn camera_instances(&self) -> Vec<CameraInstance> {
+        let mut camera_instances = Vec::new();
+        self.visit_nodes(|node, world_transform| {
+            if let Some(camera) = node.camera {
+                camera_instances.push(CameraInstance { camera, world_transform });
+            }
+        });
+        camera_instances
+    }
+
+    // every node has to be reachable from the roots only once, otherwise the hierarchy is not a forest
+    // and walking it would visit nodes repeatedly or never end
+    pub fn check_node_hierarchy(&self) -> Result<(), String> {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = self.root_nodes.clone();
+        while let Some(node_index) = stack.pop() {
+            let node = self.nodes.get(node_index)
+                .ok_or_else(|| format!("node {node_index} does not exist"))?;
+            if std::mem::replace(&mut visited[node_index], true) {
+                return Err(format!("node {node_index} is its own ancestor or has more than one parent"));
+            }
+            stack.extend(&node.children);
+        }
+        Ok(())
+    }
+
+    // depth-first from the roots, with the accumulated transform of every node;
+    // nodes already visited are skipped, see check_node_hierarchy
+    fn visit_nodes(&self, mut visit: impl FnMut(&SceneNode, Mat4)) {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = self.root_nodes.iter()
+            .rev()
+            .map(|&node| (node, Mat4::IDENTITY))
+            .collect::<Vec<_>>();
+
+        while let Some((node_index, parent_transform)) = stack.pop() {
+            if std::mem::replace(&mut visited[node_index], true) {
+                continue;
+            }
+            let node = &self.nodes[node_index];
+            let world_transform = parent_transform * node.transform;
+            visit(node, world_transform);
+            stack.extend(node.children.iter().rev().map(|&child| (child, world_transform)));
+        }
+    }
+
+    // applies the up axis and scale of the options to the root nodes
+    fn transform_roots(&mut self, options: &MeshOptions) {
+        let up = match options.up {
+            UpAxis::Y => Mat4::IDENTITY,
+            UpAxis::Z => Mat4::from_rotation_x(-std::f32::consts::FRAC_PI_2),
+        };
+        let root_transform = Mat4::from_scale(Vec3::splat(options.scale)) * up;
+        for &root_node in &self.root_nodes {
+            let node = &mut self.nodes[root_node];
+            node.transform = root_transform * node.transform;
+        }
+    }
+}
+
+// picks the importer by extension: .gltf and .glb as glTF 2.0, .ply as PLY, .stl as STL and anything else as OBJ;
+// the normals option does not apply to glTF, primitives without normals get face normals there
+pub fn load_scene(path: &Path, options: &MeshOptions) -> Result<Scene, String> {
+    let extension = path.extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_lowercase);
+
+    let mut scene = match extension.as_deref() {
+        Some("gltf" | "glb") => gltf_import::load_gltf(path)?,
+        Some("ply") => Scene::from_primitive(ply_import::load_ply(path, options.normals)?),
+        Some("stl") => Scene::from_primitive(stl_import::load_stl(path, options.normals)?),
+        // applies the up axis and scale itself
+        _ => return load_mesh(path, options).map(Scene::from_primitive),
+    };
+    scene.transform_roots(options);
+    Ok(scene)
+}
@@ -0,0 +1,100 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use vulkano::device::physical::PhysicalDeviceType;
+use vulkano::device::QueueFlags;
+use vulkano::{LoadingError, Validated, VulkanError};
+
+#[derive(Debug)]
+pub enum SetupError {
+    LibraryNotFound(LoadingError),
+    LayerEnumeration(VulkanError),
+    InstanceCreation(Validated<VulkanError>),
+    DebugCallbackCreation(Validated<VulkanError>),
+    DeviceEnumeration(VulkanError),
+    NoSuitableDevice {
+        attempted: Vec<DeviceCandidate>,
+    },
+    NoSuitableQueueFamily {
+        device: String,
+        attempted: Vec<QueueFamilyCandidate>,
+    },
+    DeviceCreation {
+        device: String,
+        error: Validated<VulkanError>,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct DeviceCandidate {
+    pub name: String,
+    pub device_type: PhysicalDeviceType,
+    pub rejection: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct QueueFamilyCandidate {
+    pub index: u32,
+    pub queue_flags: QueueFlags,
+    // None when no presentation support was requested
+    pub presentation_support: Option<bool>,
+}
+
+impl Display for SetupError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SetupError::LibraryNotFound(error) => {
+                write!(f, "No local Vulkan library/dll: {error}")
+            }
+            SetupError::LayerEnumeration(error) => {
+                write!(f, "Failed to enumerate instance layers: {error}")
+            }
+            SetupError::InstanceCreation(error) => {
+                write!(f, "Failed to create instance: {error}")
+            }
+            SetupError::DebugCallbackCreation(error) => {
+                write!(f, "Failed to create debug callback: {error}")
+            }
+            SetupError::DeviceEnumeration(error) => {
+                write!(f, "Failed to enumerate physical devices: {error}")
+            }
+            SetupError::NoSuitableDevice { attempted } => {
+                write!(f, "No suitable physical device found")?;
+                if attempted.is_empty() {
+                    write!(f, ", the instance reported no devices")?;
+                }
+                for candidate in attempted {
+                    write!(f, "\n  {} ({:?}): {}", candidate.name, candidate.device_type, candidate.rejection)?;
+                }
+                Ok(())
+            }
+            SetupError::NoSuitableQueueFamily { device, attempted } => {
+                write!(f, "No queue with appropriate support available on {device}")?;
+                for candidate in attempted {
+                    write!(f, "\n  family {}: {:?}", candidate.index, candidate.queue_flags)?;
+                    if let Some(presentation_support) = candidate.presentation_support {
+                        write!(f, ", presentation support: {presentation_support}")?;
+                    }
+                }
+                Ok(())
+            }
+            SetupError::DeviceCreation { device, error } => {
+                write!(f, "Failed to create device on {device}: {error}")
+            }
+        }
+    }
+}
+
+impl Error for SetupError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SetupError::LibraryNotFound(error) => Some(error),
+            SetupError::LayerEnumeration(error) => Some(error),
+            SetupError::InstanceCreation(error) => Some(error),
+            SetupError::DebugCallbackCreation(error) => Some(error),
+            SetupError::DeviceEnumeration(error) => Some(error),
+            SetupError::DeviceCreation { error, .. } => Some(error),
+            SetupError::NoSuitableDevice { .. }
+            | SetupError::NoSuitableQueueFamily { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use shaderc::{CompileOptions, Compiler, EnvVersion, IncludeType, ResolvedInclude, ShaderKind, TargetEnv};
+use vulkano::device::Device;
+use vulkano::shader::{ShaderModule, ShaderModuleCreateInfo};
+use vulkano::{Validated, VulkanError};
+use crate::assets;
+
+// preprocessor defines in the same (name, value) form as the define option of vulkano_shaders::shader!
+pub type ShaderDefines = [(&'static str, &'static str)];
+
+// runtime GLSL to SPIR-V compilation, for shaders that are not embedded with vulkano_shaders::shader!;
+// every (path, defines) permutation is compiled once and cached until clear_cache is called
+pub struct ShaderCompiler {
+    compiler: Compiler,
+    include_dir: PathBuf,
+    cache: HashMap<(PathBuf, Vec<(&'static str, &'static str)>), Vec<u32>>,
+}
+
+impl ShaderCompiler {
+    pub fn new() -> Result<Self, String> {
+        let compiler = Compiler::new().map_err(|error| format!("Failed to create shader compiler: {error}"))?;
+        Ok(ShaderCompiler {
+            compiler,
+            include_dir: assets::shader_path("include"),
+            cache: HashMap::new(),
+        })
+    }
+
+    // a path is assumed to always be compiled as the same kind of shader
+    // errors are returned as the compiler output, ready to be shown to the user
+    pub fn compile_file(&mut self, path: &Path, kind: ShaderKind, defines: &ShaderDefines) -> Result<Vec<u32>, String> {
+        let key = (path.to_path_buf(), defines.to_vec());
+        if let Some(spirv) = self.cache.get(&key) {
+            return Ok(spirv.clone());
+        }
+
+        let source = fs::read_to_string(path)
+            .map_err(|error| format!("Failed to read {}: {error}", path.display()))?;
+
+        let mut options = CompileOptions::new().map_err(|error| error.to_string())?;
+        options.set_target_env(TargetEnv::Vulkan, EnvVersion::Vulkan1_2 as u32);
+        for (name, value) in defines {
+            options.add_macro_definition(name, Some(value));
+        }
+        let include_dir = self.include_dir.clone();
+        options.set_include_callback(move |requested_source, include_type, requesting_source, _depth| {
+            resolve_include(&include_dir, requested_source, include_type, requesting_source)
+        });
+
+        let artifact = self.compiler
+            .compile_into_spirv(&source, kind, &path.display().to_string(), "main", Some(&options))
+            .map_err(|error| error.to_string())?;
+
+        let spirv = artifact.as_binary().to_vec();
+        self.cache.insert(key, spirv.clone());
+        Ok(spirv)
+    }
+
+    // has to be called when a source or include file changed
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+}
+
+// "file" is looked up next to the including file first, <file> only in the include directory
+fn resolve_include(include_dir: &Path,
+                   requested_source: &str,
+                   include_type: IncludeType,
+                   requesting_source: &str
+) -> Result<ResolvedInclude, String> {
+    let relative_candidate = match include_type {
+        IncludeType::Relative => Path::new(requesting_source).parent()
+            .map(|requesting_dir| requesting_dir.join(requested_source))
+            .filter(|candidate| candidate.is_file()),
+        IncludeType::Standard => None,
+    };
+    let path = relative_candidate.unwrap_or_else(|| include_dir.join(requested_source));
+
+    let content = fs::read_to_string(&path)
+        .map_err(|error| format!("Failed to include {}: {error}", path.display()))?;
+
+    Ok(ResolvedInclude {
+        resolved_name: path.display().to_string(),
+        content,
+    })
+}
+
+pub fn load_shader_module(device: Arc<Device>, spirv: &[u32]) -> Result<Arc<ShaderModule>, Validated<VulkanError>> {
+    // the SPIR-V comes straight from shaderc, which only emits valid modules
+    unsafe {
+        ShaderModule::new(device, ShaderModuleCreateInfo::new(spirv))
+    }
+}
@@ -0,0 +1,135 @@
+use std::fs;
+use std::path::Path;
+use glam::Vec3;
+use crate::mesh::NormalSource;
+use crate::mesh_processing::{generate_normals, NormalMode};
+use crate::scene::{Primitive, SceneVertex};
+
+// 80 byte header and a u32 triangle count, then 50 bytes per triangle
+const BINARY_HEADER_SIZE: usize = 84;
+const BINARY_TRIANGLE_SIZE: usize = 50;
+
+// STL stores unconnected triangles with one normal each, so the face normals are those of the file
+pub(crate) fn load_stl(path: &Path, normals: Option<NormalSource>) -> Result<Primitive, String> {
+    let bytes = fs::read(path).map_err(|error| format!("Failed to open {}: {error}", path.display()))?;
+    let triangles = if is_binary(&bytes) {
+        parse_binary(&bytes)
+    } else {
+        parse_ascii(&bytes)
+    }.map_err(|error| format!("Failed to parse {}: {error}", path.display()))?;
+
+    if triangles.is_empty() {
+        return Err(format!("Failed to parse {}: no triangles", path.display()));
+    }
+
+    Ok(match normals.unwrap_or(NormalSource::File) {
+        NormalSource::File => file_normals(&triangles),
+        NormalSource::Face => with_generated_normals(&triangles, NormalMode::Flat),
+        NormalSource::Vertex => with_generated_normals(&triangles, NormalMode::AreaWeighted),
+    })
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Triangle {
+    normal: [f32; 3],
+    positions: [[f32; 3]; 3],
+}
+
+// ASCII files start with "solid" as well, but a binary header may too, so the size decides
+fn is_binary(bytes: &[u8]) -> bool {
+    let Some(count) = bytes.get(80..BINARY_HEADER_SIZE) else {
+        return false;
+    };
+    let count = u32::from_le_bytes(count.try_into().unwrap()) as usize;
+    count.checked_mul(BINARY_TRIANGLE_SIZE)
+        .and_then(|size| size.checked_add(BINARY_HEADER_SIZE))
+        == Some(bytes.len())
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<Vec<Triangle>, String> {
+    let read_vec3 = |chunk: &[u8]| -> [f32; 3] {
+        [0, 1, 2].map(|i| f32::from_le_bytes(chunk[i * 4..i * 4 + 4].try_into().unwrap()))
+    };
+
+    Ok(bytes[BINARY_HEADER_SIZE..].chunks_exact(BINARY_TRIANGLE_SIZE)
+        // 12 floats followed by a u16 attribute count that nothing uses
+        .map(|chunk| Triangle {
+            normal: read_vec3(&chunk[0..12]),
+            positions: [1, 2, 3].map(|i| read_vec3(&chunk[i * 12..i * 12 + 12])),
+        })
+        .collect())
+}
+
+fn parse_ascii(bytes: &[u8]) -> Result<Vec<Triangle>, String> {
+    let text = std::str::from_utf8(bytes).map_err(|_| "neither binary nor ASCII STL")?;
+    let mut words = text.split_whitespace();
+    if words.next() != Some("solid") {
+        return Err("not an STL file".to_string());
+    }
+
+    let read_vec3 = |words: &mut std::str::SplitWhitespace| -> Result<[f32; 3], String> {
+        let mut vector = [0.0; 3];
+        for component in &mut vector {
+            let word = words.next().ok_or("unexpected end of file")?;
+            *component = word.parse().map_err(|_| format!("invalid number {word}"))?;
+        }
+        Ok(vector)
+    };
+
+    let mut triangles = Vec::new();
+    let mut normal = [0.0; 3];
+    let mut positions = Vec::with_capacity(3);
+    while let Some(word) = words.next() {
+        match word {
+            "normal" => normal = read_vec3(&mut words)?,
+            "vertex" => positions.push(read_vec3(&mut words)?),
+            "endfacet" => {
+                let positions = std::mem::take(&mut positions);
+                let positions: [[f32; 3]; 3] = positions.try_into()
+                    .map_err(|positions: Vec<_>| format!("a facet has {} vertices instead of 3", positions.len()))?;
+                triangles.push(Triangle { normal, positions });
+                normal = [0.0; 3];
+            }
+            // the name after solid and endsolid, and the facet, outer, loop and endloop keywords
+            _ => {}
+        }
+    }
+    Ok(triangles)
+}
+
+fn unwelded(triangles: &[Triangle]) -> Primitive {
+    Primitive {
+        vertices: triangles.iter()
+            .flat_map(|triangle| triangle.positions.map(|position| SceneVertex {
+                position,
+                normal: triangle.normal,
+                ..Default::default()
+            }))
+            .collect(),
+        indices: (0..triangles.len() as u32 * 3).collect(),
+        material: None,
+    }
+}
+
+// many exporters write zero normals, those triangles get computed ones
+fn file_normals(triangles: &[Triangle]) -> Primitive {
+    let mut primitive = unwelded(triangles);
+    for vertices in primitive.vertices.chunks_exact_mut(3) {
+        if Vec3::from_array(vertices[0].normal).length_squared() > 0.0 {
+            continue;
+        }
+        let [a, b, c] = [0, 1, 2].map(|i| Vec3::from_array(vertices[i].position));
+        let normal = (b - a).cross(c - a).normalize_or_zero().to_array();
+        for vertex in vertices {
+            vertex.normal = normal;
+        }
+    }
+    primitive
+}
+
+// generated normals smooth over triangles at identical positions, so the loose triangles get connected
+fn with_generated_normals(triangles: &[Triangle], mode: NormalMode) -> Primitive {
+    let mut primitive = unwelded(triangles);
+    generate_normals(&mut primitive, mode);
+    primitive
+}
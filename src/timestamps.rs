@@ -0,0 +1,164 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+use log::warn;
+use vulkano::command_buffer::AutoCommandBufferBuilder;
+use vulkano::device::Device;
+use vulkano::query::{QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType};
+use vulkano::sync::PipelineStage;
+use vulkano::{Validated, ValidationError, VulkanError};
+
+// named GPU timing scopes within one command buffer, backed by a timestamp query pool;
+// the pool is reused, so results have to be read before the next reset
+pub struct TimestampQueries {
+    query_pool: Arc<QueryPool>,
+    max_scopes: u32,
+    timestamp_period: f64,
+    valid_bits_mask: u64,
+    scope_names: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TimestampScope(u32);
+
+// callers drop the measurement on these instead of failing
+#[derive(Debug)]
+pub enum TimestampError {
+    TooManyScopes(u32),
+    Recording(Box<ValidationError>),
+    Readback(Validated<VulkanError>),
+}
+
+impl Display for TimestampError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimestampError::TooManyScopes(max_scopes) => write!(f, "No more than {max_scopes} timestamp scopes per reset"),
+            TimestampError::Recording(error) => write!(f, "Failed to record timestamp queries: {error}"),
+            TimestampError::Readback(error) => write!(f, "Failed to read timestamp queries: {error}"),
+        }
+    }
+}
+
+impl Error for TimestampError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TimestampError::TooManyScopes(_) => None,
+            TimestampError::Recording(error) => Some(error),
+            TimestampError::Readback(error) => Some(error),
+        }
+    }
+}
+
+impl TimestampQueries {
+    // None when the queue family does not support timestamps or the query pool cannot be created
+    pub fn new(device: Arc<Device>, queue_family_index: u32, max_scopes: u32) -> Option<Self> {
+        let physical_device = device.physical_device().clone();
+        let valid_bits = physical_device.queue_family_properties()[queue_family_index as usize]
+            .timestamp_valid_bits
+            .filter(|valid_bits| *valid_bits > 0)?;
+
+        let query_pool = QueryPool::new(
+            device,
+            QueryPoolCreateInfo {
+                query_count: max_scopes * 2,
+                ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+            }
+        )
+            .inspect_err(|error| warn!("Failed to create query pool, continuing without GPU timings: {error}"))
+            .ok()?;
+
+        Some(TimestampQueries {
+            query_pool,
+            max_scopes,
+            timestamp_period: physical_device.properties().timestamp_period as f64,
+            valid_bits_mask: if valid_bits >= 64 { u64::MAX } else { (1u64 << valid_bits) - 1 },
+            scope_names: Vec::new(),
+        })
+    }
+
+    // has to be recorded outside of render passes, before the first scope
+    pub fn reset<L>(&mut self, command_buffer_builder: &mut AutoCommandBufferBuilder<L>) -> Result<(), TimestampError> {
+        unsafe {
+            command_buffer_builder
+                .reset_query_pool(self.query_pool.clone(), 0..self.max_scopes * 2)
+                .map_err(TimestampError::Recording)?;
+        }
+        self.scope_names.clear();
+        Ok(())
+    }
+
+    pub fn begin_scope<L>(&mut self, command_buffer_builder: &mut AutoCommandBufferBuilder<L>, name: impl Into<String>
+    ) -> Result<TimestampScope, TimestampError> {
+        let index = self.scope_names.len() as u32;
+        if index >= self.max_scopes {
+            return Err(TimestampError::TooManyScopes(self.max_scopes));
+        }
+
+        unsafe {
+            command_buffer_builder
+                .write_timestamp(self.query_pool.clone(), index * 2, PipelineStage::TopOfPipe)
+                .map_err(TimestampError::Recording)?;
+        }
+        // only once the query is recorded, reading an unwritten one would wait forever
+        self.scope_names.push(name.into());
+        Ok(TimestampScope(index))
+    }
+
+    pub fn end_scope<L>(&self, command_buffer_builder: &mut AutoCommandBufferBuilder<L>, scope: TimestampScope
+    ) -> Result<(), TimestampError> {
+        unsafe {
+            command_buffer_builder
+                .write_timestamp(self.query_pool.clone(), scope.0 * 2 + 1, PipelineStage::BottomOfPipe)
+                .map_err(TimestampError::Recording)?;
+        }
+        Ok(())
+    }
+
+    pub fn scoped<L, R>(&mut self,
+                        command_buffer_builder: &mut AutoCommandBufferBuilder<L>,
+                        name: impl Into<String>,
+                        record: impl FnOnce(&mut AutoCommandBufferBuilder<L>) -> R
+    ) -> Result<R, TimestampError> {
+        let scope = self.begin_scope(command_buffer_builder, name)?;
+        let result = record(command_buffer_builder);
+        self.end_scope(command_buffer_builder, scope)?;
+        Ok(result)
+    }
+
+    // blocks until the GPU has written every scope, so the command buffer has to be submitted already
+    pub fn results(&self) -> Result<Vec<(String, Duration)>, TimestampError> {
+        self.read(QueryResultFlags::WAIT).map(Option::unwrap_or_default)
+    }
+
+    // None while the GPU has not finished every scope, and when the results cannot be read
+    pub fn try_results(&self) -> Option<Vec<(String, Duration)>> {
+        self.read(QueryResultFlags::empty())
+            .inspect_err(|error| warn!("{error}"))
+            .ok()
+            .flatten()
+    }
+
+    fn read(&self, flags: QueryResultFlags) -> Result<Option<Vec<(String, Duration)>>, TimestampError> {
+        if self.scope_names.is_empty() {
+            return Ok(Some(Vec::new()));
+        }
+
+        let query_count = self.scope_names.len() as u32 * 2;
+        let mut timestamps = vec![0u64; query_count as usize];
+        let available = self.query_pool
+            .get_results(0..query_count, &mut timestamps, flags)
+            .map_err(TimestampError::Readback)?;
+        if !available {
+            return Ok(None);
+        }
+
+        Ok(Some(self.scope_names.iter()
+            .zip(timestamps.chunks_exact(2))
+            .map(|(name, timestamps)| {
+                let ticks = timestamps[1].wrapping_sub(timestamps[0]) & self.valid_bits_mask;
+                (name.clone(), Duration::from_nanos((ticks as f64 * self.timestamp_period) as u64))
+            })
+            .collect()))
+    }
+}
@@ -0,0 +1,133 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+use log::{error, info, warn};
+use serde::Serialize;
+use vulkano::instance::debug::DebugUtilsMessageSeverity;
+use crate::logging::DebugMessage;
+
+const REPORT_ENV_VAR: &str = "VKPG_VALIDATION_REPORT";
+
+// counts the ERROR and WARNING messages of the debug messenger, other severities are ignored
+pub struct ValidationReport {
+    // false when the layers were not loaded, nothing is counted then
+    enabled: bool,
+    errors: AtomicU32,
+    warnings: AtomicU32,
+    messages: Mutex<Vec<ReportedMessage>>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ReportedMessage {
+    pub severity: &'static str,
+    pub id_name: Option<String>,
+    pub id_number: i32,
+}
+
+// why a headless run failed, see ValidationReport::finish_run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidationFailure {
+    Errors(u32),
+    ReportNotWritten,
+}
+
+#[derive(Serialize)]
+struct ReportSummary<'a> {
+    validation: &'static str,
+    errors: u32,
+    warnings: u32,
+    messages: &'a [ReportedMessage],
+}
+
+impl ValidationReport {
+    pub fn new(enabled: bool) -> Self {
+        ValidationReport {
+            enabled,
+            errors: AtomicU32::new(0),
+            warnings: AtomicU32::new(0),
+            messages: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn record(&self, message: &DebugMessage) {
+        let severity = if message.severity.intersects(DebugUtilsMessageSeverity::ERROR) {
+            self.errors.fetch_add(1, Ordering::AcqRel);
+            "error"
+        } else if message.severity.intersects(DebugUtilsMessageSeverity::WARNING) {
+            self.warnings.fetch_add(1, Ordering::AcqRel);
+            "warning"
+        } else {
+            return;
+        };
+
+        self.messages.lock().unwrap().push(ReportedMessage {
+            severity,
+            id_name: message.id_name.map(str::to_string),
+            id_number: message.id_number,
+        });
+    }
+
+    pub fn error_count(&self) -> u32 {
+        self.errors.load(Ordering::Acquire)
+    }
+
+    pub fn warning_count(&self) -> u32 {
+        self.warnings.load(Ordering::Acquire)
+    }
+
+    pub fn messages(&self) -> Vec<ReportedMessage> {
+        self.messages.lock().unwrap().clone()
+    }
+
+    pub fn to_json(&self) -> String {
+        let messages = self.messages.lock().unwrap();
+        serde_json::to_string_pretty(&ReportSummary {
+            validation: if self.enabled { "enabled" } else { "disabled" },
+            errors: self.error_count(),
+            warnings: self.warning_count(),
+            messages: &messages,
+        }).unwrap()
+    }
+
+    pub fn write_json(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_json())
+    }
+
+    // for headless runs: logs the totals and writes the report to VKPG_VALIDATION_REPORT when set.
+    // Fails when any validation error occurred, a run without the layers passes but says so
+    pub fn finish_run(&self) -> Result<(), ValidationFailure> {
+        if self.enabled {
+            info!("Validation finished with {} errors and {} warnings", self.error_count(), self.warning_count());
+        } else {
+            warn!("Validation layers were not loaded, no validation errors were checked");
+        }
+
+        if let Ok(path) = env::var(REPORT_ENV_VAR)
+            && let Err(write_error) = self.write_json(&path) {
+            error!("Failed to write validation report to {}: {}", path, write_error);
+            return Err(ValidationFailure::ReportNotWritten);
+        }
+
+        match self.error_count() {
+            0 => Ok(()),
+            errors => Err(ValidationFailure::Errors(errors)),
+        }
+    }
+}
+
+impl ValidationFailure {
+    pub fn exit_code(self) -> ExitCode {
+        match self {
+            ValidationFailure::Errors(_) => ExitCode::from(1),
+            ValidationFailure::ReportNotWritten => ExitCode::from(2),
+        }
+    }
+}
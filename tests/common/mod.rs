@@ -0,0 +1,18 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use vulkan_playground::mesh::MeshOptions;
+use vulkan_playground::scene::{load_scene, Primitive};
+
+// writes the file with the extension that picks the importer, and returns the first primitive of the first mesh
+pub fn load_primitive(name: &str, extension: &str, bytes: &[u8]) -> Result<Primitive, String> {
+    let path = temporary_path(name, extension);
+    fs::write(&path, bytes).unwrap();
+    let scene = load_scene(&path, &MeshOptions::default());
+    fs::remove_file(&path).unwrap();
+    scene.map(|mut scene| scene.meshes.remove(0).primitives.remove(0))
+}
+
+fn temporary_path(name: &str, extension: &str) -> PathBuf {
+    env::temp_dir().join(format!("vulkan_playground_{}_{name}.{extension}", std::process::id()))
+}
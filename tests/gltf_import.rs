@@ -0,0 +1,50 @@
+mod common;
+
+use common::load_primitive;
+
+// a triangle, TEXCOORD_0 is zero everywhere and TEXCOORD_1 matches the positions;
+// the buffer and the 1x1 white PNG are embedded as base64
+const TWO_TEX_COORD_SETS: &str = r#"{
+    "asset": { "version": "2.0" },
+    "scene": 0,
+    "scenes": [{ "nodes": [0] }],
+    "nodes": [{ "mesh": 0 }],
+    "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0, "TEXCOORD_0": 1, "TEXCOORD_1": 2 }, "material": 0 }] }],
+    "materials": [{ "pbrMetallicRoughness": { "baseColorTexture": { "index": 0, "texCoord": 1 } } }],
+    "textures": [{ "source": 0 }],
+    "images": [{ "uri": "data:image/png;base64,iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAAC0lEQVR4nGP4DwQACfsD/fteaysAAAAASUVORK5CYII=" }],
+    "accessors": [
+        { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3", "min": [0, 0, 0], "max": [1, 1, 0] },
+        { "bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC2" },
+        { "bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC2" }
+    ],
+    "bufferViews": [
+        { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+        { "buffer": 0, "byteOffset": 36, "byteLength": 24 },
+        { "buffer": 0, "byteOffset": 60, "byteLength": 24 }
+    ],
+    "buffers": [{ "byteLength": 84, "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAIA/AAAAAAAAAAAAAIA/" }]
+}"#;
+
+// node 1 is a child of node 0 and the other way around
+const CYCLIC_NODES: &str = r#"{
+    "asset": { "version": "2.0" },
+    "scene": 0,
+    "scenes": [{ "nodes": [0] }],
+    "nodes": [{ "children": [1] }, { "children": [0] }]
+}"#;
+
+#[test]
+fn gltf_base_color_texture_uses_its_tex_coord_set() {
+    let primitive = load_primitive("tex_coord_set", "gltf", TWO_TEX_COORD_SETS.as_bytes()).unwrap();
+    let tex_coords = primitive.vertices.iter()
+        .map(|vertex| vertex.tex_coord)
+        .collect::<Vec<_>>();
+    assert_eq!(tex_coords, [[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
+}
+
+#[test]
+fn gltf_with_cyclic_nodes_is_an_error() {
+    let error = load_primitive("cyclic_nodes", "gltf", CYCLIC_NODES.as_bytes()).unwrap_err();
+    assert!(error.contains("node hierarchy"), "error is {error}");
+}
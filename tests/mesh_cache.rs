@@ -0,0 +1,78 @@
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use vulkan_playground::mesh::MeshOptions;
+use vulkan_playground::mesh_cache::{load_scene_cached, MeshCacheMode};
+use vulkan_playground::scene::{load_scene, Scene};
+
+// the same length, so only the modification time or the hash can tell them apart
+const TRIANGLE: &str = "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+const WIDER_TRIANGLE: &str = "v 0 0 0\nv 2 0 0\nv 0 1 0\nf 1 2 3\n";
+
+// a directory of its own, so the cache written next to the source does not clash with other tests
+fn source_path(name: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("vulkan_playground_{}_{name}", std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    directory.join("triangle.obj")
+}
+
+fn write_source(path: &Path, contents: &str, modified: SystemTime) {
+    fs::write(path, contents).unwrap();
+    File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+}
+
+fn load_cached(path: &Path) -> Scene {
+    load_scene_cached(path, &MeshOptions::default(), MeshCacheMode::NextToSource).unwrap()
+}
+
+fn positions(scene: &Scene) -> Vec<[f32; 3]> {
+    scene.meshes[0].primitives[0].vertices.iter().map(|vertex| vertex.position).collect()
+}
+
+#[test]
+fn mesh_cache_is_rejected_when_the_source_changes() {
+    let path = source_path("mesh_cache_changed");
+    let modified = SystemTime::now();
+    write_source(&path, TRIANGLE, modified);
+    load_cached(&path);
+    assert!(path.with_file_name("triangle.obj.vkpgmesh").exists());
+
+    write_source(&path, WIDER_TRIANGLE, modified + Duration::from_secs(10));
+    let expected = load_scene(&path, &MeshOptions::default()).unwrap();
+    assert_eq!(positions(&load_cached(&path)), positions(&expected));
+
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+// small sources are hashed on every load, so the cache is not fooled by a time that was kept
+#[test]
+fn mesh_cache_is_rejected_when_the_source_changes_without_a_new_time() {
+    let path = source_path("mesh_cache_changed_in_place");
+    let modified = SystemTime::now();
+    write_source(&path, TRIANGLE, modified);
+    load_cached(&path);
+
+    write_source(&path, WIDER_TRIANGLE, modified);
+    let expected = load_scene(&path, &MeshOptions::default()).unwrap();
+    assert_eq!(positions(&load_cached(&path)), positions(&expected));
+
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn mesh_cache_is_accepted_after_a_touch() {
+    let path = source_path("mesh_cache_touched");
+    let modified = SystemTime::now();
+    write_source(&path, TRIANGLE, modified);
+    let original = positions(&load_cached(&path));
+    let cache_path = path.with_file_name("triangle.obj.vkpgmesh");
+    let cache_len = fs::metadata(&cache_path).unwrap().len();
+
+    write_source(&path, TRIANGLE, modified + Duration::from_secs(10));
+    assert_eq!(positions(&load_cached(&path)), original);
+    assert_eq!(fs::metadata(&cache_path).unwrap().len(), cache_len);
+
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+}
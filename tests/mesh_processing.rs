@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use glam::Vec3;
+use obj::raw::object::Polygon;
+use obj::raw::RawObj;
+use vulkan_playground::assets::resource_path;
+use vulkan_playground::mesh_processing::{corner_normals, generate_normals, NormalMode};
+use vulkan_playground::scene::{Primitive, SceneVertex};
+
+// the baked normals are written with four decimals
+const FLAT_TOLERANCE_DEGREES: f32 = 0.05;
+const SMOOTH_TOLERANCE_DEGREES: f32 = 0.5;
+const BUNNY_POSITIONS: usize = 2503;
+const BUNNY_TRIANGLES: usize = 4968;
+
+fn parse(name: &str) -> RawObj {
+    let file = File::open(resource_path(name)).unwrap();
+    obj::raw::parse_obj(BufReader::new(file)).unwrap()
+}
+
+// the baked bunnies are bunny.obj scaled by 10 and turned half a turn around Y, with the triangles in the same order;
+// the positions are turned the same way here, scaling does not change normals
+fn bunny() -> (Vec<Vec3>, Vec<u32>) {
+    let raw_obj = parse("bunny.obj");
+    let positions = raw_obj.positions.iter()
+        .map(|&(x, y, z, _)| Vec3::new(-x, y, -z))
+        .collect::<Vec<_>>();
+    let indices = raw_obj.polygons.iter()
+        .flat_map(|polygon| match polygon {
+            Polygon::P(corners) => corners.iter().map(|&corner| corner as u32).collect::<Vec<_>>(),
+            _ => panic!("bunny.obj should only have positions"),
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(positions.len(), BUNNY_POSITIONS);
+    assert_eq!(indices.len(), BUNNY_TRIANGLES * 3);
+    (positions, indices)
+}
+
+// per triangle, the position index and normal of every corner
+fn baked_triangles(name: &str) -> Vec<[(usize, Vec3); 3]> {
+    let raw_obj = parse(name);
+    raw_obj.polygons.iter()
+        .map(|polygon| match polygon {
+            Polygon::PN(corners) => {
+                let corners = corners.iter()
+                    .map(|&(position, normal)| {
+                        let (x, y, z) = raw_obj.normals[normal];
+                        (position, Vec3::new(x, y, z))
+                    })
+                    .collect::<Vec<_>>();
+                corners.try_into().expect("the baked bunnies should be triangulated")
+            }
+            _ => panic!("{name} should have normals for every corner"),
+        })
+        .collect()
+}
+
+// the face normals bunny has one triangle twice, so its normals are looked up by the positions of the triangle
+fn baked_face_normals() -> HashMap<[usize; 3], Vec3> {
+    baked_triangles("bunny_face_normals.obj").into_iter()
+        .map(|corners| {
+            let mut positions = corners.map(|(position, _)| position);
+            positions.sort();
+            (positions, corners[0].1)
+        })
+        .collect()
+}
+
+fn angle_degrees(a: Vec3, b: Vec3) -> f32 {
+    a.angle_between(b).to_degrees()
+}
+
+fn assert_flat(indices: &[u32], normals: &[Vec3]) {
+    let baked_normals = baked_face_normals();
+    for (triangle, normals) in indices.chunks_exact(3).zip(normals.chunks_exact(3)) {
+        let mut positions = [0, 1, 2].map(|i| triangle[i] as usize);
+        positions.sort();
+        let baked_normal = baked_normals[&positions];
+        for &normal in normals {
+            let angle = angle_degrees(normal, baked_normal);
+            assert!(angle < FLAT_TOLERANCE_DEGREES, "triangle {triangle:?} is off by {angle} degrees");
+        }
+    }
+}
+
+fn assert_smooth(normals: &[Vec3]) {
+    let baked_triangles = baked_triangles("bunny_vertex_normals.obj");
+    assert_eq!(baked_triangles.len(), BUNNY_TRIANGLES);
+    for (corner, (&normal, (position, baked_normal))) in normals.iter().zip(baked_triangles.into_iter().flatten()).enumerate() {
+        let angle = angle_degrees(normal, baked_normal);
+        assert!(angle < SMOOTH_TOLERANCE_DEGREES, "corner {corner} at position {position} is off by {angle} degrees");
+    }
+}
+
+fn bunny_primitive() -> Primitive {
+    let (positions, indices) = bunny();
+    Primitive {
+        vertices: positions.into_iter()
+            .map(|position| SceneVertex {
+                position: position.to_array(),
+                ..Default::default()
+            })
+            .collect(),
+        indices,
+        material: None,
+    }
+}
+
+#[test]
+fn flat_normals_match_baked_face_normals() {
+    let (positions, indices) = bunny();
+    let normals = corner_normals(&positions, &indices, NormalMode::Flat);
+    assert_flat(&indices, &normals);
+}
+
+// Blender weights by corner angle
+#[test]
+fn angle_weighted_normals_match_baked_vertex_normals() {
+    let (positions, indices) = bunny();
+    assert_smooth(&corner_normals(&positions, &indices, NormalMode::AngleWeighted));
+}
+
+// there is no baked area-weighted bunny, but on a mesh this even it stays close to the angle-weighted one
+#[test]
+fn area_weighted_normals_are_close_to_baked_vertex_normals() {
+    let (positions, indices) = bunny();
+    let normals = corner_normals(&positions, &indices, NormalMode::AreaWeighted);
+    let baked_triangles = baked_triangles("bunny_vertex_normals.obj");
+
+    let mut angles = normals.iter()
+        .zip(baked_triangles.into_iter().flatten())
+        .map(|(&normal, (_, baked_normal))| angle_degrees(normal, baked_normal))
+        .collect::<Vec<_>>();
+    angles.sort_by(f32::total_cmp);
+    assert!(angles[angles.len() / 2] < 3.0, "the median is off by {} degrees", angles[angles.len() / 2]);
+    assert!(normals.iter().all(|normal| normal.is_normalized()));
+}
+
+#[test]
+fn crease_angle_of_180_degrees_is_smooth() {
+    let (positions, indices) = bunny();
+    assert_smooth(&corner_normals(&positions, &indices, NormalMode::Crease { angle_degrees: 180.0 }));
+}
+
+#[test]
+fn crease_angle_of_0_degrees_is_flat() {
+    let (positions, indices) = bunny();
+    assert_flat(&indices, &corner_normals(&positions, &indices, NormalMode::Crease { angle_degrees: 0.0 }));
+}
+
+// an average of normals within the crease angle of the face normal cannot be further away from it
+#[test]
+fn crease_normals_stay_within_crease_angle() {
+    let (positions, indices) = bunny();
+    let crease_normals = corner_normals(&positions, &indices, NormalMode::Crease { angle_degrees: 30.0 });
+    let flat_normals = corner_normals(&positions, &indices, NormalMode::Flat);
+    for (crease_normal, flat_normal) in crease_normals.into_iter().zip(flat_normals) {
+        assert!(angle_degrees(crease_normal, flat_normal) <= 30.0 + 1e-3);
+    }
+}
+
+#[test]
+fn generated_normals_split_and_merge_vertices() {
+    let mut primitive = bunny_primitive();
+    generate_normals(&mut primitive, NormalMode::Flat);
+    assert_eq!(primitive.indices.len(), BUNNY_TRIANGLES * 3);
+    assert!(primitive.vertices.len() > BUNNY_POSITIONS);
+
+    // smoothing the flat bunny welds it back together
+    generate_normals(&mut primitive, NormalMode::AngleWeighted);
+    assert_eq!(primitive.vertices.len(), BUNNY_POSITIONS);
+
+    let mut creased = bunny_primitive();
+    generate_normals(&mut creased, NormalMode::Crease { angle_degrees: 30.0 });
+    assert!(creased.vertices.len() >= BUNNY_POSITIONS);
+    assert!(creased.vertices.len() <= BUNNY_TRIANGLES * 3);
+}
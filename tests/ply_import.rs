@@ -0,0 +1,105 @@
+mod common;
+
+use vulkan_playground::scene::Primitive;
+
+// a quad with normals and 8-bit colors
+const POSITIONS: [[f32; 3]; 4] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]];
+const NORMAL: [f32; 3] = [0.0, 0.0, 1.0];
+const COLORS: [[u8; 4]; 4] = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 128], [255, 255, 255, 0]];
+
+fn header(format: &str) -> String {
+    format!("ply\n\
+             format {format} 1.0\n\
+             comment written by the tests\n\
+             element vertex 4\n\
+             property float x\nproperty float y\nproperty float z\n\
+             property float nx\nproperty float ny\nproperty float nz\n\
+             property uchar red\nproperty uchar green\nproperty uchar blue\nproperty uchar alpha\n\
+             element face 1\n\
+             property list uchar int vertex_indices\n\
+             end_header\n")
+}
+
+fn ascii_ply() -> Vec<u8> {
+    let mut text = header("ascii");
+    for (position, color) in POSITIONS.iter().zip(COLORS) {
+        let [x, y, z] = position;
+        let [nx, ny, nz] = NORMAL;
+        let [red, green, blue, alpha] = color;
+        text += &format!("{x} {y} {z} {nx} {ny} {nz} {red} {green} {blue} {alpha}\n");
+    }
+    text += "4 0 1 2 3\n";
+    text.into_bytes()
+}
+
+fn binary_ply(format: &str, f32_bytes: fn(f32) -> [u8; 4], i32_bytes: fn(i32) -> [u8; 4]) -> Vec<u8> {
+    let mut bytes = header(format).into_bytes();
+    for (position, color) in POSITIONS.iter().zip(COLORS) {
+        for &value in position.iter().chain(&NORMAL) {
+            bytes.extend_from_slice(&f32_bytes(value));
+        }
+        bytes.extend_from_slice(&color);
+    }
+    bytes.push(4);
+    for index in 0..4 {
+        bytes.extend_from_slice(&i32_bytes(index));
+    }
+    bytes
+}
+
+fn load_ply(name: &str, bytes: &[u8]) -> Result<Primitive, String> {
+    common::load_primitive(name, "ply", bytes)
+}
+
+fn assert_quad(primitive: &Primitive) {
+    // the quad is split into a fan
+    assert_eq!(primitive.indices, [0, 1, 2, 0, 2, 3]);
+    assert_eq!(primitive.vertices.len(), 4);
+
+    for ((vertex, position), color) in primitive.vertices.iter().zip(POSITIONS).zip(COLORS) {
+        assert_eq!(vertex.position, position);
+        assert_eq!(vertex.normal, NORMAL);
+        // 0 and 255 are the same in sRGB and linear, and alpha is not converted
+        for (channel, expected) in vertex.color.into_iter().zip(color) {
+            assert!((channel - expected as f32 / 255.0).abs() < 1e-6, "color is {:?}", vertex.color);
+        }
+    }
+}
+
+#[test]
+fn ascii_ply_with_uchar_colors() {
+    assert_quad(&load_ply("ascii", &ascii_ply()).unwrap());
+}
+
+#[test]
+fn binary_little_endian_ply_with_uchar_colors() {
+    let bytes = binary_ply("binary_little_endian", f32::to_le_bytes, i32::to_le_bytes);
+    assert_quad(&load_ply("binary_little_endian", &bytes).unwrap());
+}
+
+#[test]
+fn binary_big_endian_ply_with_uchar_colors() {
+    let bytes = binary_ply("binary_big_endian", f32::to_be_bytes, i32::to_be_bytes);
+    assert_quad(&load_ply("binary_big_endian", &bytes).unwrap());
+}
+
+// the counts in the header must not be trusted for preallocation
+#[test]
+fn ply_with_huge_element_counts_is_an_error() {
+    let huge_vertex_count = String::from_utf8(ascii_ply()).unwrap()
+        .replace("element vertex 4", &format!("element vertex {}", usize::MAX));
+    assert!(load_ply("huge_vertex_count", huge_vertex_count.as_bytes()).is_err());
+
+    let huge_face_count = String::from_utf8(ascii_ply()).unwrap()
+        .replace("element face 1", &format!("element face {}", usize::MAX / 2));
+    assert!(load_ply("huge_face_count", huge_face_count.as_bytes()).is_err());
+}
+
+#[test]
+fn ply_with_invalid_vertex_indices_is_an_error() {
+    let negative_index = String::from_utf8(ascii_ply()).unwrap().replace("4 0 1 2 3", "4 0 1 2 -1");
+    assert!(load_ply("negative_index", negative_index.as_bytes()).is_err());
+
+    let index_past_the_end = String::from_utf8(ascii_ply()).unwrap().replace("4 0 1 2 3", "4 0 1 2 4");
+    assert!(load_ply("index_past_the_end", index_past_the_end.as_bytes()).is_err());
+}
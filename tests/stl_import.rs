@@ -0,0 +1,79 @@
+mod common;
+
+use vulkan_playground::scene::Primitive;
+
+// two facets, the second without a normal like many exporters write them
+const TRIANGLES: [([f32; 3], [[f32; 3]; 3]); 2] = [
+    ([0.0, 0.0, 1.0], [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]),
+    ([0.0, 0.0, 0.0], [[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]]),
+];
+// the normal computed for the second facet, from its winding
+const COMPUTED_NORMAL: [f32; 3] = [0.0, 1.0, 0.0];
+
+fn ascii_stl() -> Vec<u8> {
+    let mut text = "solid test\n".to_string();
+    for (normal, positions) in TRIANGLES {
+        let [nx, ny, nz] = normal;
+        text += &format!("  facet normal {nx} {ny} {nz}\n    outer loop\n");
+        for [x, y, z] in positions {
+            text += &format!("      vertex {x} {y} {z}\n");
+        }
+        text += "    endloop\n  endfacet\n";
+    }
+    text += "endsolid test\n";
+    text.into_bytes()
+}
+
+fn binary_stl(header_text: &[u8]) -> Vec<u8> {
+    let mut bytes = header_text.to_vec();
+    bytes.resize(80, 0);
+    bytes.extend_from_slice(&(TRIANGLES.len() as u32).to_le_bytes());
+    for (normal, positions) in TRIANGLES {
+        for value in normal.into_iter().chain(positions.into_iter().flatten()) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        // attribute byte count
+        bytes.extend_from_slice(&[0, 0]);
+    }
+    bytes
+}
+
+fn load_stl(name: &str, bytes: &[u8]) -> Result<Primitive, String> {
+    common::load_primitive(name, "stl", bytes)
+}
+
+fn assert_triangles(primitive: &Primitive) {
+    // STL triangles are not connected, so every corner is its own vertex
+    assert_eq!(primitive.indices, [0, 1, 2, 3, 4, 5]);
+
+    let expected_normals = [TRIANGLES[0].0, COMPUTED_NORMAL];
+    for ((vertices, (_, positions)), normal) in primitive.vertices.chunks_exact(3).zip(TRIANGLES).zip(expected_normals) {
+        for (vertex, position) in vertices.iter().zip(positions) {
+            assert_eq!(vertex.position, position);
+            assert_eq!(vertex.normal, normal);
+        }
+    }
+}
+
+#[test]
+fn ascii_stl_with_file_and_missing_normals() {
+    assert_triangles(&load_stl("ascii", &ascii_stl()).unwrap());
+}
+
+#[test]
+fn binary_stl_with_file_and_missing_normals() {
+    assert_triangles(&load_stl("binary", &binary_stl(b"binary STL written by the tests")).unwrap());
+}
+
+// the size decides, a header starting with solid does not make it ASCII
+#[test]
+fn binary_stl_with_header_starting_with_solid() {
+    assert_triangles(&load_stl("binary_solid", &binary_stl(b"solid written by the tests")).unwrap());
+}
+
+#[test]
+fn truncated_binary_stl_is_an_error() {
+    let mut bytes = binary_stl(b"binary STL written by the tests");
+    bytes.truncate(bytes.len() - 10);
+    assert!(load_stl("truncated", &bytes).is_err());
+}